@@ -0,0 +1,49 @@
+//! Processes growing batches of transactions for a single client and prints the time spent
+//! per transaction. With id-indexed transactions the time per transaction stays flat.
+//!
+//! Run with `cargo bench`.
+
+use std::time::Instant;
+use transactions_test::{Engine, Tx, TxType, Value};
+
+const SIZES: [u32; 4] = [100_000, 200_000, 400_000, 800_000];
+
+fn main() {
+    println!("{:>10} {:>12} {:>10}", "deposits", "total ms", "ns/tx");
+
+    for &size in SIZES.iter() {
+        let transactions = transactions(size);
+        let mut engine = Engine::new();
+        let mut messages = vec![];
+        let start = Instant::now();
+
+        for tx in &transactions {
+            engine.process(tx, &mut messages);
+        }
+
+        let elapsed = start.elapsed();
+
+        assert!(messages.is_empty());
+
+        println!(
+            "{:>10} {:>12} {:>10}",
+            size,
+            elapsed.as_millis(),
+            elapsed.as_nanos() / transactions.len() as u128
+        );
+    }
+}
+
+/// Deposits followed by a dispute and resolve of every deposit, in reverse order.
+fn transactions(size: u32) -> Vec<Tx> {
+    let amount = Value::from_units(10_000);
+    let deposits = (1..=size).map(|id| Tx::new(TxType::Deposit, 1, id, amount));
+    let disputes = (1..=size).rev().flat_map(|id| {
+        vec![
+            Tx::new(TxType::Dispute, 1, id, Value::ZERO),
+            Tx::new(TxType::Resolve, 1, id, Value::ZERO),
+        ]
+    });
+
+    deposits.chain(disputes).collect()
+}
@@ -0,0 +1,274 @@
+use anyhow::{bail, Result};
+use transactions_test::{
+    Config, DisputePolicy, EventFormat, LockPolicy, SortOrder, WithdrawalDisputePolicy,
+};
+
+/// Format of the client balances output.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Csv,
+    /// Single JSON array
+    Json,
+    /// JSON object per line
+    JsonLines,
+}
+
+/// Command line arguments.
+#[derive(Debug, Default)]
+pub struct Args {
+    /// Input CSV file with transactions
+    pub input: String,
+    /// Optional CSV file to collect rejected input records
+    pub rejects: Option<String>,
+    /// Optional engine state file to continue processing from
+    pub state: Option<String>,
+    /// Optional file to save engine state to after processing
+    pub save_state: Option<String>,
+    /// Optional write-ahead journal of processed transactions, requires `save_state`
+    pub journal: Option<String>,
+    /// Number of worker threads, transactions are processed on the main thread if less than 2
+    pub threads: usize,
+    pub output_format: OutputFormat,
+    /// Optional file to write engine messages to as machine-readable events
+    pub events: Option<String>,
+    pub events_format: EventFormat,
+    pub sort: SortOrder,
+    /// Optional JSON file with the fee schedule
+    pub fees: Option<String>,
+    /// Optional CSV or JSON file with client profiles
+    pub profiles: Option<String>,
+    /// Optional CSV file with exchange rates
+    pub rates: Option<String>,
+    /// Engine settings
+    pub config: Config,
+}
+
+impl Args {
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self> {
+        let mut result = Self::default();
+        let mut input = None;
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--rejects" => result.rejects = Some(value(&arg, args.next())?),
+                "--state" => result.state = Some(value(&arg, args.next())?),
+                "--save-state" => result.save_state = Some(value(&arg, args.next())?),
+                "--journal" => result.journal = Some(value(&arg, args.next())?),
+                "--threads" => match value(&arg, args.next())?.parse() {
+                    Ok(threads) if threads > 0 => result.threads = threads,
+                    _ => bail!("ERROR: Option '{}' expects a positive number.", arg),
+                },
+                "--output-format" => {
+                    result.output_format = choice(
+                        &arg,
+                        args.next(),
+                        &[
+                            ("csv", OutputFormat::Csv),
+                            ("json", OutputFormat::Json),
+                            ("jsonl", OutputFormat::JsonLines),
+                        ],
+                    )?
+                }
+                "--events" => result.events = Some(value(&arg, args.next())?),
+                "--events-format" => {
+                    result.events_format = choice(
+                        &arg,
+                        args.next(),
+                        &[("jsonl", EventFormat::JsonLines), ("csv", EventFormat::Csv)],
+                    )?
+                }
+                "--dispute-policy" => {
+                    result.config.dispute_policy = choice(
+                        &arg,
+                        args.next(),
+                        &[
+                            ("reject", DisputePolicy::Reject),
+                            ("negative", DisputePolicy::Negative),
+                            ("clawback", DisputePolicy::ClawBack),
+                        ],
+                    )?
+                }
+                "--withdrawal-dispute-policy" => {
+                    result.config.withdrawal_dispute_policy = choice(
+                        &arg,
+                        args.next(),
+                        &[
+                            ("disallow", WithdrawalDisputePolicy::Disallow),
+                            ("credit", WithdrawalDisputePolicy::HoldCredit),
+                            ("legacy", WithdrawalDisputePolicy::Legacy),
+                        ],
+                    )?
+                }
+                "--lock-policy" => {
+                    result.config.lock_policy = choice(
+                        &arg,
+                        args.next(),
+                        &[
+                            ("full", LockPolicy::Full),
+                            ("debit", LockPolicy::DebitOnly),
+                            ("none", LockPolicy::None),
+                        ],
+                    )?
+                }
+                "--max-disputes" => match value(&arg, args.next())?.parse() {
+                    Ok(max_disputes) if max_disputes > 0 => {
+                        result.config.max_disputes = max_disputes
+                    }
+                    _ => bail!("ERROR: Option '{}' expects a positive number.", arg),
+                },
+                "--profiles" => result.profiles = Some(value(&arg, args.next())?),
+                "--fees" => result.fees = Some(value(&arg, args.next())?),
+                "--rates" => result.rates = Some(value(&arg, args.next())?),
+                "--strict" => result.config.strict = true,
+                "--admin" => result.config.admin_operations = true,
+                "--sort" => {
+                    result.sort = choice(
+                        &arg,
+                        args.next(),
+                        &[("client", SortOrder::Client), ("none", SortOrder::None)],
+                    )?
+                }
+                _ if arg.starts_with("--") => bail!("ERROR: Unknown option '{}'.", arg),
+                _ if input.is_none() => input = Some(arg),
+                _ => bail!("ERROR: Unexpected argument '{}'.", arg),
+            }
+        }
+
+        match input {
+            Some(input) => result.input = input,
+            None => bail!("ERROR: Expected CSV file as input parameter."),
+        }
+
+        if result.journal.is_some() {
+            // Journal is cleared once its entries are saved in the state
+            if result.save_state.is_none() {
+                bail!("ERROR: Option '--journal' requires '--save-state'.");
+            }
+            if result.threads > 1 {
+                bail!("ERROR: Option '--journal' cannot be used with '--threads'.");
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+fn value(option: &str, value: Option<String>) -> Result<String> {
+    match value {
+        Some(value) => Ok(value),
+        None => bail!("ERROR: Option '{}' expects a value.", option),
+    }
+}
+
+fn choice<T: Copy>(option: &str, value: Option<String>, choices: &[(&str, T)]) -> Result<T> {
+    let value = self::value(option, value)?;
+
+    match choices.iter().find(|(name, _)| *name == value) {
+        Some((_, choice)) => Ok(*choice),
+        None => bail!(
+            "ERROR: Option '{}' expects one of: {}.",
+            option,
+            choices
+                .iter()
+                .map(|(name, _)| *name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Args> {
+        Args::parse(args.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn test_parse_defaults() {
+        let args = parse("input.csv").unwrap();
+
+        assert_eq!("input.csv", args.input);
+        assert_eq!(0, args.threads);
+        assert_eq!(OutputFormat::Csv, args.output_format);
+        assert_eq!(EventFormat::JsonLines, args.events_format);
+        assert_eq!(SortOrder::Client, args.sort);
+        assert_eq!(None, args.journal);
+        assert!(!args.config.strict);
+    }
+
+    #[test]
+    fn test_parse() {
+        let args = parse(
+            "--rejects rejects.csv --state in.json --save-state out.json --journal journal \
+             --output-format jsonl --events events.csv --events-format csv \
+             --dispute-policy clawback --withdrawal-dispute-policy credit --lock-policy debit \
+             --max-disputes 3 --profiles profiles.json --fees fees.json --rates rates.csv \
+             --strict --admin --sort none input.csv",
+        )
+        .unwrap();
+
+        assert_eq!("input.csv", args.input);
+        assert_eq!(Some("rejects.csv"), args.rejects.as_deref());
+        assert_eq!(Some("in.json"), args.state.as_deref());
+        assert_eq!(Some("out.json"), args.save_state.as_deref());
+        assert_eq!(Some("journal"), args.journal.as_deref());
+        assert_eq!(OutputFormat::JsonLines, args.output_format);
+        assert_eq!(Some("events.csv"), args.events.as_deref());
+        assert_eq!(EventFormat::Csv, args.events_format);
+        assert_eq!(DisputePolicy::ClawBack, args.config.dispute_policy);
+        assert_eq!(
+            WithdrawalDisputePolicy::HoldCredit,
+            args.config.withdrawal_dispute_policy
+        );
+        assert_eq!(LockPolicy::DebitOnly, args.config.lock_policy);
+        assert_eq!(3, args.config.max_disputes);
+        assert_eq!(Some("profiles.json"), args.profiles.as_deref());
+        assert_eq!(Some("fees.json"), args.fees.as_deref());
+        assert_eq!(Some("rates.csv"), args.rates.as_deref());
+        assert!(args.config.strict);
+        assert!(args.config.admin_operations);
+        assert_eq!(SortOrder::None, args.sort);
+    }
+
+    #[test]
+    fn test_parse_threads() {
+        assert_eq!(4, parse("--threads 4 input.csv").unwrap().threads);
+
+        for args in &[
+            "--threads 0 input.csv",
+            "--threads -1 input.csv",
+            "--threads x input.csv",
+            "input.csv --threads",
+        ] {
+            assert!(parse(args).is_err(), "{}", args);
+        }
+    }
+
+    #[test]
+    fn test_parse_fail() {
+        for args in &[
+            "",
+            "input.csv other.csv",
+            "--unknown input.csv",
+            "--sort id input.csv",
+            "--output-format xml input.csv",
+            "--max-disputes 0 input.csv",
+            "--journal journal input.csv",
+            "--journal journal --save-state out.json --threads 2 input.csv",
+        ] {
+            assert!(parse(args).is_err(), "{}", args);
+        }
+    }
+
+    #[test]
+    fn test_parse_error_message() {
+        assert_eq!(
+            "ERROR: Option '--sort' expects one of: client, none.",
+            parse("--sort id input.csv").unwrap_err().to_string()
+        );
+    }
+}
@@ -1,42 +1,89 @@
 use crate::{
-    common::{ClientId, Value},
-    message::Message,
-    transaction::{Tx, TxState, TxType},
+    common::ClientId,
+    config::{Config, DisputePolicy, LockPolicy, WithdrawalDisputePolicy},
+    message::{Message, Severity},
+    transaction::{DisputeCycle, FundsChange, Tx, TxDetails, TxId, TxState, TxType},
+    value::Value,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 
-#[derive(Serialize, Default)]
+/// Status of the client account.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccountStatus {
+    #[default]
+    Active,
+    /// Locked by a chargeback
+    Locked,
+    /// Frozen by an operator
+    Frozen,
+    /// Closed by an operator, cannot be unlocked
+    Closed,
+}
+
+/// Change of funds in the currency, `None` is the account currency.
+type Change = (FundsChange, Option<String>);
+
+/// Funds of the client in one currency.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Balance {
+    pub available: Value,
+    pub held: Value,
+    pub total: Value,
+}
+
+#[derive(Default, Serialize, Deserialize)]
 pub struct Client {
+    pub id: ClientId,
+    /// Funds in the account currency, or in the implicit currency if the account has none
+    #[serde(flatten)]
+    pub balance: Balance,
+    /// Funds in other currencies by currency code
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub balances: BTreeMap<String, Balance>,
+    pub status: AccountStatus,
+    /// Available funds may go negative down to minus this limit
+    pub credit_limit: Value,
+    /// Currency of the account
+    pub currency: Option<String>,
+    /// Date the account was created, e.g. `2021-03-31`
+    pub created: Option<String>,
+
+    /// Client's deposit and withdrawal transactions indexed by id
+    pub transactions: HashMap<TxId, Tx>,
+    /// Ids of client's deposit and withdrawal transactions in processing order
+    pub tx_order: Vec<TxId>,
+    /// Accepted administrative operations and charged fees in processing order
+    pub operations: Vec<Tx>,
+}
+
+/// Point in time view of the client account balances.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClientSnapshot {
     #[serde(rename = "client")]
     pub id: ClientId,
+    /// Currency of the balances, `None` for the implicit currency
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
     pub available: Value,
     pub held: Value,
     pub total: Value,
     pub locked: bool,
-
-    /// Client's deposit and withdrawal transactions
-    #[serde(skip)]
-    pub transactions: Vec<Tx>,
-}
-
-impl PartialEq for Client {
-    fn eq(&self, other: &Self) -> bool {
-        self.id == other.id
-            && self.available == other.available
-            && self.held == other.held
-            && self.total == other.total
-            && self.locked == other.locked
-    }
+    /// Remaining credit, only for clients with a credit limit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credit: Option<Value>,
 }
 
 impl std::fmt::Debug for Client {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Client")
             .field("id", &self.id)
-            .field("a", &self.available)
-            .field("h", &self.held)
-            .field("t", &self.total)
-            .field("l", &self.locked)
+            .field("a", &self.balance.available)
+            .field("h", &self.balance.held)
+            .field("t", &self.balance.total)
+            .field("s", &self.status)
+            .field("b", &self.balances)
             .finish()
     }
 }
@@ -49,101 +96,793 @@ impl Client {
         }
     }
 
-    pub fn process(&mut self, tx: &Tx, messages: &mut Vec<Message>) {
-        if !self.validate(tx, messages) {
-            return;
+    /// Balances in the account currency.
+    pub fn snapshot(&self) -> ClientSnapshot {
+        ClientSnapshot {
+            id: self.id,
+            currency: self.currency.clone(),
+            available: self.balance.available,
+            held: self.balance.held,
+            total: self.balance.total,
+            locked: self.status != AccountStatus::Active,
+            credit: self.credit_limit.is_positive().then(|| self.credit()),
+        }
+    }
+
+    /// Balances in every currency, the account currency first. Empty balance in the account
+    /// currency is left out if the client has funds in other currencies.
+    pub fn snapshots(&self) -> Vec<ClientSnapshot> {
+        let mut snapshots = vec![];
+
+        if self.balances.is_empty() || self.balance != Balance::default() {
+            snapshots.push(self.snapshot());
+        }
+
+        snapshots.extend(
+            self.balances
+                .iter()
+                .map(|(currency, balance)| ClientSnapshot {
+                    id: self.id,
+                    currency: Some(currency.clone()),
+                    available: balance.available,
+                    held: balance.held,
+                    total: balance.total,
+                    locked: self.status != AccountStatus::Active,
+                    credit: None,
+                }),
+        );
+
+        snapshots
+    }
+
+    /// Remaining credit, negative if the credit limit is exceeded. Credit is given only in the
+    /// account currency.
+    pub fn credit(&self) -> Value {
+        if self.balance.available < Value::ZERO {
+            // Sum of values with opposite signs cannot overflow
+            self.credit_limit
+                .checked_add(self.balance.available)
+                .unwrap_or_default()
+        } else {
+            self.credit_limit
+        }
+    }
+
+    /// Funds in the `currency`, `None` is the account currency.
+    pub fn balance(&self, currency: Option<&str>) -> Balance {
+        match self.other_currency(currency) {
+            Some(currency) => self.balances.get(currency).copied().unwrap_or_default(),
+            None => self.balance,
+        }
+    }
+
+    fn balance_mut(&mut self, currency: Option<&str>) -> &mut Balance {
+        match self.other_currency(currency) {
+            Some(currency) => self.balances.entry(currency.to_string()).or_default(),
+            None => &mut self.balance,
+        }
+    }
+
+    /// Returns the `currency` if it's not the account currency.
+    fn other_currency<'a>(&self, currency: Option<&'a str>) -> Option<&'a str> {
+        currency.filter(|c| Some(*c) != self.currency.as_deref())
+    }
+
+    /// Returns `true` if transaction was accepted, rejected transaction reports an error.
+    pub fn process(&mut self, tx: &Tx, config: &Config, messages: &mut Vec<Message>) -> bool {
+        let count = messages.len();
+
+        if !self.validate(tx, config, messages) {
+            return false;
         }
 
         match tx.ty {
             TxType::Deposit => {
-                self.available += tx.amount;
-                self.total += tx.amount;
-                self.transactions.push(tx.clone());
-            }
-            TxType::Withdrawal => {
-                if self.available < tx.amount || self.total < tx.amount {
-                    messages.push(Message::NotEnoughFunds(tx.client_id, tx.tx_id, tx.ty));
-                } else {
-                    self.available -= tx.amount;
-                    self.total -= tx.amount;
-                    self.transactions.push(tx.clone());
+                let changes = vec![Some((
+                    FundsChange::credit(tx.amount),
+                    tx.currency().map(str::to_string),
+                ))];
+
+                if self.apply_changes(tx, changes, messages) {
+                    self.record(tx);
                 }
             }
-            TxType::Dispute => match self.transactions.iter_mut().find(|t| t.tx_id == tx.tx_id) {
-                Some(t) => match t.state {
-                    TxState::Active => {
-                        let amount = t.dispute_amount();
-                        self.available -= amount;
-                        self.held += amount;
-                        t.state = TxState::InDispute;
+            TxType::Withdrawal => {
+                let fee = config.fees.withdrawal(tx.amount);
+
+                match fee.and_then(|fee| tx.amount.checked_add(fee).map(|debit| (fee, debit))) {
+                    Some((_, debit)) if !self.can_debit(debit, tx.currency()) => {
+                        messages.push(self.not_enough_funds(tx, tx.currency()));
                     }
-                    TxState::InDispute => {
-                        messages.push(Message::AlreadyInDispute(tx.client_id, tx.tx_id, t.ty));
+                    Some((fee, _)) => {
+                        let changes = [tx.amount, fee]
+                            .iter()
+                            .map(|amount| {
+                                FundsChange::debit(*amount)
+                                    .map(|c| (c, tx.currency().map(str::to_string)))
+                            })
+                            .collect();
+
+                        if self.apply_changes(tx, changes, messages) {
+                            self.record(tx);
+                            self.record_fee(tx, fee, tx.currency());
+                        }
                     }
-                    TxState::Disputed => {
-                        messages.push(Message::AlreadyDisputed(tx.client_id, tx.tx_id, t.ty));
+                    None => {
+                        messages.push(Message::ValueOverflow(tx.client_id, tx.tx_id, tx.ty));
                     }
-                },
-                None => {
-                    messages.push(Message::UnknownTransaction(tx.client_id, tx.tx_id));
                 }
-            },
-            TxType::Resolve => match self.transactions.iter_mut().find(|t| t.tx_id == tx.tx_id) {
-                Some(t) => match t.state {
-                    TxState::Active => {
-                        messages.push(Message::NotInDispute(tx.client_id, tx.tx_id, t.ty));
+            }
+            TxType::Transfer => unreachable!("Transfer is processed with its destination"),
+            TxType::Exchange => self.exchange(tx, config, messages),
+            TxType::Dispute => self.dispute(tx, config, messages),
+            TxType::Resolve | TxType::Chargeback => self.close_dispute(tx, config, messages),
+            TxType::Unlock | TxType::Freeze | TxType::Close => self.admin(tx, config, messages),
+            TxType::Fee => {
+                messages.push(Message::SystemTransaction(tx.client_id, tx.tx_id, tx.ty));
+            }
+        }
+
+        // eprintln!("INFO: {:?} -> {:?}", tx, self);
+
+        accepted(&messages[count..])
+    }
+
+    /// Processes transaction that changes funds of this client and the `destination`: transfer
+    /// from this client, or dispute, resolve or chargeback of such transfer. Transaction is
+    /// rejected as a whole if it cannot be applied to any of the clients.
+    pub fn process_transfer(
+        &mut self,
+        destination: &mut Client,
+        tx: &Tx,
+        config: &Config,
+        messages: &mut Vec<Message>,
+    ) -> bool {
+        let count = messages.len();
+
+        if !self.validate(tx, config, messages)
+            || !destination.check_status(tx, false, config, messages)
+        {
+            return false;
+        }
+
+        match tx.ty {
+            TxType::Transfer => self.transfer(destination, tx, messages),
+            TxType::Dispute => {
+                if let Some(disputed) = self.dispute_amount(tx, config, messages) {
+                    let t = &self.transactions[&tx.tx_id];
+                    let change =
+                        t.dispute_change(tx.ty, disputed, config.withdrawal_dispute_policy);
+                    let currency = self.transfer_currency(t).map(str::to_string);
+
+                    // Transferred funds are held on the destination account
+                    if let Some(clawed_back) =
+                        destination.hold(tx, TxType::Transfer, change, currency, config, messages)
+                    {
+                        self.open_dispute(tx.tx_id, disputed, clawed_back);
                     }
-                    TxState::InDispute => {
-                        let amount = t.dispute_amount();
-                        self.available += amount;
-                        self.held -= amount;
-                        t.state = TxState::Disputed;
+                }
+            }
+            TxType::Resolve | TxType::Chargeback => {
+                if let Some((index, amount)) = self.open_cycle(tx, messages) {
+                    let t = &self.transactions[&tx.tx_id];
+                    let currency = self.transfer_currency(t).map(str::to_string);
+                    let change = t.dispute_change(tx.ty, amount, config.withdrawal_dispute_policy);
+                    let fee = config.fees.chargeback(amount);
+                    let clawed_back = t.disputes[index].clawed_back.clone();
+                    let mut changes = vec![change.map(|c| (c, currency.clone()))];
+                    let mut returned = vec![];
+
+                    // Withdrawals of the destination clawed back by the dispute are restored
+                    if tx.ty == TxType::Resolve {
+                        changes.extend(destination.restored_changes(&clawed_back));
                     }
-                    TxState::Disputed => {
-                        messages.push(Message::AlreadyDisputed(tx.client_id, tx.tx_id, t.ty));
+
+                    // Charged back funds return to the source
+                    if tx.ty == TxType::Chargeback {
+                        changes.push(
+                            fee.and_then(FundsChange::debit)
+                                .map(|c| (c, currency.clone())),
+                        );
+                        returned.push(Some((FundsChange::credit(amount), currency.clone())));
                     }
-                },
-                None => {
-                    messages.push(Message::UnknownTransaction(tx.client_id, tx.tx_id));
-                }
-            },
-            TxType::Chargeback => {
-                match self.transactions.iter_mut().find(|t| t.tx_id == tx.tx_id) {
-                    Some(t) => match t.state {
-                        TxState::Active => {
-                            messages.push(Message::NotInDispute(tx.client_id, tx.tx_id, t.ty));
+
+                    match (
+                        self.changed_balances(returned),
+                        destination.changed_balances(changes),
+                    ) {
+                        (Some(source_balances), Some(destination_balances)) => {
+                            self.set_balances(source_balances);
+                            destination.set_balances(destination_balances);
+                            self.close_cycle(tx.tx_id, index, tx.ty);
+
+                            if tx.ty == TxType::Resolve {
+                                destination.restore(&clawed_back);
+                            }
+
+                            if tx.ty == TxType::Chargeback {
+                                destination.status = AccountStatus::Locked;
+                                destination.record_fee(
+                                    tx,
+                                    fee.unwrap_or_default(),
+                                    currency.as_deref(),
+                                );
+                            }
                         }
-                        TxState::InDispute => {
-                            let amount = t.dispute_amount();
-                            self.held -= amount;
-                            self.total -= amount;
-                            t.state = TxState::Disputed;
-                            self.locked = true;
+                        (_, None) => {
+                            messages.push(Message::ValueOverflow(destination.id, tx.tx_id, tx.ty));
                         }
-                        TxState::Disputed => {
-                            messages.push(Message::AlreadyDisputed(tx.client_id, tx.tx_id, t.ty));
+                        (None, _) => {
+                            messages.push(Message::ValueOverflow(self.id, tx.tx_id, tx.ty));
                         }
-                    },
-                    None => {
-                        messages.push(Message::UnknownTransaction(tx.client_id, tx.tx_id));
                     }
                 }
             }
+            _ => unreachable!(),
         }
 
-        // eprintln!("INFO: {:?} -> {:?}", tx, self);
+        accepted(&messages[count..])
+    }
+
+    fn transfer(&mut self, destination: &mut Client, tx: &Tx, messages: &mut Vec<Message>) {
+        let currency = self.transfer_currency(tx).map(str::to_string);
+
+        if !self.can_debit(tx.amount, currency.as_deref()) {
+            messages.push(self.not_enough_funds(tx, currency.as_deref()));
+            return;
+        }
+
+        let source = vec![FundsChange::debit(tx.amount).map(|c| (c, currency.clone()))];
+        let received = vec![Some((FundsChange::credit(tx.amount), currency))];
+
+        match (
+            self.changed_balances(source),
+            destination.changed_balances(received),
+        ) {
+            (Some(source_balances), Some(destination_balances)) => {
+                self.set_balances(source_balances);
+                destination.set_balances(destination_balances);
+                destination.record(tx);
+                self.record(tx);
+            }
+            (_, None) => {
+                messages.push(Message::ValueOverflow(destination.id, tx.tx_id, tx.ty));
+            }
+            (None, _) => {
+                messages.push(Message::ValueOverflow(self.id, tx.tx_id, tx.ty));
+            }
+        }
+    }
+
+    /// Converts the amount from the transaction currency to the target currency with the rate
+    /// valid on the transaction date. Applied rate is recorded with the transaction.
+    fn exchange(&mut self, tx: &Tx, config: &Config, messages: &mut Vec<Message>) {
+        let from = tx
+            .currency()
+            .map(str::to_string)
+            .or_else(|| self.currency.clone());
+        let to = tx.to_currency().map(str::to_string);
+        let rate = match (from.as_deref(), to.as_deref()) {
+            (Some(from), Some(to)) if from != to => config.rates.rate(from, to, tx.date()),
+            _ => None,
+        };
+        let rate = match rate {
+            Some(rate) => rate,
+            None => {
+                messages.push(Message::RateNotFound(tx.client_id, tx.tx_id, tx.ty));
+                return;
+            }
+        };
+
+        if !self.can_debit(tx.amount, from.as_deref()) {
+            messages.push(self.not_enough_funds(tx, from.as_deref()));
+            return;
+        }
+
+        let changes = vec![
+            FundsChange::debit(tx.amount).map(|c| (c, from.clone())),
+            tx.amount
+                .checked_convert(rate)
+                .map(|amount| (FundsChange::credit(amount), to)),
+        ];
+
+        if self.apply_changes(tx, changes, messages) {
+            let mut tx = tx.clone();
+            let details = tx.details_mut();
+
+            details.currency = from;
+            details.rate = Some(rate);
+            self.record(&tx);
+        }
+    }
+
+    /// Currency of the transfer from this client, transfer without currency moves funds in
+    /// the account currency of the source.
+    fn transfer_currency<'a>(&'a self, tx: &'a Tx) -> Option<&'a str> {
+        tx.currency().or(self.currency.as_deref())
+    }
+
+    /// Records the `fee` charged for the transaction in the client history. Fee can make
+    /// available funds negative, e.g. the chargeback penalty.
+    fn record_fee(&mut self, tx: &Tx, fee: Value, currency: Option<&str>) {
+        if fee.is_positive() {
+            self.operations
+                .push(
+                    Tx::new(TxType::Fee, self.id, tx.tx_id, fee).with_details(TxDetails {
+                        currency: currency.map(str::to_string),
+                        ..TxDetails::default()
+                    }),
+                );
+        }
+    }
+
+    /// Credit limit in the `currency`, credit is given only in the account currency.
+    fn credit_limit(&self, currency: Option<&str>) -> Value {
+        match self.other_currency(currency) {
+            Some(_) => Value::ZERO,
+            None => self.credit_limit,
+        }
+    }
+
+    /// Checks that funds in the `currency` and the credit limit cover the `amount`.
+    fn can_debit(&self, amount: Value, currency: Option<&str>) -> bool {
+        let balance = self.balance(currency);
+        let credit_limit = self.credit_limit(currency);
+
+        [balance.available, balance.total].iter().all(|funds| {
+            funds
+                .checked_add(credit_limit)
+                .is_none_or(|funds| funds >= amount)
+        })
+    }
+
+    fn not_enough_funds(&self, tx: &Tx, currency: Option<&str>) -> Message {
+        if self.credit_limit(currency).is_positive() {
+            Message::CreditLimitExceeded(tx.client_id, tx.tx_id, tx.ty)
+        } else {
+            Message::NotEnoughFunds(tx.client_id, tx.tx_id, tx.ty)
+        }
+    }
+
+    /// Adds transaction to the client history.
+    fn record(&mut self, tx: &Tx) {
+        self.transactions.insert(tx.tx_id, tx.clone());
+        self.tx_order.push(tx.tx_id);
+    }
+
+    fn dispute(&mut self, tx: &Tx, config: &Config, messages: &mut Vec<Message>) {
+        let disputed = match self.dispute_amount(tx, config, messages) {
+            Some(disputed) => disputed,
+            None => return,
+        };
+        let t = &self.transactions[&tx.tx_id];
+        let ty = t.ty;
+        let change = t.dispute_change(tx.ty, disputed, config.withdrawal_dispute_policy);
+        let currency = t.funds(disputed).1.map(str::to_string);
+
+        if let Some(clawed_back) = self.hold(tx, ty, change, currency, config, messages) {
+            self.open_dispute(tx.tx_id, disputed, clawed_back);
+        }
+    }
+
+    /// Checks that the transaction can be disputed and returns the disputed amount.
+    fn dispute_amount(
+        &self,
+        tx: &Tx,
+        config: &Config,
+        messages: &mut Vec<Message>,
+    ) -> Option<Value> {
+        let t = match self.transactions.get(&tx.tx_id) {
+            Some(t) => t,
+            None => {
+                messages.push(Message::UnknownTransaction(tx.client_id, tx.tx_id));
+                return None;
+            }
+        };
+
+        if !self.check_currency(tx, t, messages) {
+            return None;
+        }
+
+        match t.state {
+            TxState::Active | TxState::InDispute | TxState::Resolved => (),
+            TxState::ChargedBack => {
+                messages.push(Message::AlreadyChargedBack(tx.client_id, tx.tx_id, t.ty));
+                return None;
+            }
+            TxState::ClawedBack => {
+                messages.push(Message::AlreadyClawedBack(tx.client_id, tx.tx_id, t.ty));
+                return None;
+            }
+        }
+
+        let undisputed = t
+            .open_disputes()
+            .and_then(|open| t.amount.checked_sub(open))
+            .unwrap_or_default();
+
+        if !undisputed.is_positive() {
+            messages.push(Message::AlreadyInDispute(tx.client_id, tx.tx_id, t.ty));
+            return None;
+        }
+
+        if t.ty == TxType::Exchange && tx.amount.is_positive() && tx.amount != t.amount {
+            messages.push(Message::PartialExchangeDispute(
+                tx.client_id,
+                tx.tx_id,
+                t.ty,
+            ));
+            return None;
+        }
+
+        // Dispute without amount takes the whole undisputed part
+        let disputed = if tx.amount.is_positive() {
+            tx.amount
+        } else {
+            undisputed
+        };
+
+        if disputed > undisputed {
+            messages.push(Message::DisputeAmountExceeded(tx.client_id, tx.tx_id, t.ty));
+            return None;
+        }
+
+        if !t.can_dispute(disputed, config.max_disputes) {
+            messages.push(Message::DisputeLimitReached(tx.client_id, tx.tx_id, t.ty));
+            return None;
+        }
+
+        if t.ty == TxType::Withdrawal
+            && config.withdrawal_dispute_policy == WithdrawalDisputePolicy::Disallow
+        {
+            messages.push(Message::WithdrawalDisputeNotAllowed(
+                tx.client_id,
+                tx.tx_id,
+                t.ty,
+            ));
+            return None;
+        }
+
+        Some(disputed)
+    }
+
+    /// Checks that the dispute, resolve or chargeback `tx` is in the currency of the disputed
+    /// transaction `t`, if the currency is given.
+    fn check_currency(&self, tx: &Tx, t: &Tx, messages: &mut Vec<Message>) -> bool {
+        let currency = match t.ty {
+            TxType::Transfer => self.transfer_currency(t),
+            _ => t.currency(),
+        };
+
+        if tx.currency().is_some()
+            && self.other_currency(tx.currency()) != self.other_currency(currency)
+        {
+            messages.push(Message::CurrencyMismatch(tx.client_id, tx.tx_id, t.ty));
+            return false;
+        }
+
+        true
+    }
+
+    /// Applies the `change` of funds in the `currency` caused by the dispute of transaction of
+    /// type `ty`, `None` change is an overflow. Returns ids of the withdrawals clawed back to
+    /// cover the dispute, `None` if the dispute is rejected because available funds cannot
+    /// cover it or a balance would overflow.
+    fn hold(
+        &mut self,
+        tx: &Tx,
+        ty: TxType,
+        change: Option<FundsChange>,
+        currency: Option<String>,
+        config: &Config,
+        messages: &mut Vec<Message>,
+    ) -> Option<Vec<TxId>> {
+        let (change, amount) = match change.and_then(|c| Some((c, c.available.checked_neg()?))) {
+            Some(change) => change,
+            None => {
+                messages.push(Message::ValueOverflow(self.id, tx.tx_id, tx.ty));
+                return None;
+            }
+        };
+        let available = self.balance(currency.as_deref()).available;
+        let mut clawed_back = vec![];
+        let mut negative = false;
+
+        // Funds of the disputed transaction were already spent
+        if amount.is_positive() && amount > available {
+            match config.dispute_policy {
+                DisputePolicy::Reject => {
+                    messages.push(Message::NotEnoughFundsForDispute(self.id, tx.tx_id, ty));
+                    return None;
+                }
+                DisputePolicy::Negative => (),
+                DisputePolicy::ClawBack => {
+                    clawed_back = self.claw_back(tx.tx_id, amount, currency.as_deref())
+                }
+            }
+
+            let returned = clawed_back
+                .iter()
+                .try_fold(available, |sum, (_, amount)| sum.checked_add(*amount));
+
+            negative = matches!(returned, Some(available) if amount > available);
+        }
+
+        let mut changes = vec![Some((change, currency.clone()))];
+
+        changes.extend(
+            clawed_back
+                .iter()
+                .map(|(_, amount)| Some((FundsChange::credit(*amount), currency.clone()))),
+        );
+
+        if !self.apply_changes(tx, changes, messages) {
+            return None;
+        }
+
+        for (id, _) in &clawed_back {
+            if let Some(t) = self.transactions.get_mut(id) {
+                t.state = TxState::ClawedBack;
+            }
+
+            messages.push(Message::ClawedBack(self.id, *id, TxType::Withdrawal));
+        }
+
+        if negative {
+            messages.push(Message::NegativeAvailable(self.id, tx.tx_id, ty));
+        }
+
+        Some(clawed_back.into_iter().map(|(id, _)| id).collect())
+    }
+
+    fn open_dispute(&mut self, tx_id: TxId, amount: Value, clawed_back: Vec<TxId>) {
+        if let Some(t) = self.transactions.get_mut(&tx_id) {
+            t.disputes.push(DisputeCycle {
+                amount,
+                outcome: None,
+                clawed_back,
+            });
+            t.update_state();
+        }
+    }
+
+    /// Changes that withdraw the clawed back withdrawals again.
+    fn restored_changes(&self, clawed_back: &[TxId]) -> Vec<Option<Change>> {
+        clawed_back
+            .iter()
+            .filter_map(|id| self.transactions.get(id))
+            .map(|t| FundsChange::debit(t.amount).map(|c| (c, t.currency().map(str::to_string))))
+            .collect()
+    }
+
+    /// Returns clawed back withdrawals to the state before the dispute.
+    fn restore(&mut self, clawed_back: &[TxId]) {
+        for id in clawed_back {
+            if let Some(t) = self.transactions.get_mut(id) {
+                t.update_state();
+            }
+        }
+    }
+
+    fn close_dispute(&mut self, tx: &Tx, config: &Config, messages: &mut Vec<Message>) {
+        let (index, amount) = match self.open_cycle(tx, messages) {
+            Some(cycle) => cycle,
+            None => return,
+        };
+        let t = &self.transactions[&tx.tx_id];
+        let change = t.dispute_change(tx.ty, amount, config.withdrawal_dispute_policy);
+        let (funds, currency) = t.funds(amount);
+        let currency = currency.map(str::to_string);
+        // Fee is charged in the currency of the disputed funds, so it's based on their amount
+        let fee = config.fees.chargeback(funds);
+        let clawed_back = t.disputes[index].clawed_back.clone();
+        let mut changes = vec![change.map(|c| (c, currency.clone()))];
+
+        // Resolved dispute restores withdrawals clawed back to cover it, chargeback keeps
+        // them reversed
+        if tx.ty == TxType::Resolve {
+            changes.extend(self.restored_changes(&clawed_back));
+        }
+
+        if tx.ty == TxType::Chargeback {
+            // Charged back exchange returns the converted funds in the source currency
+            if t.ty == TxType::Exchange {
+                changes.push(Some((
+                    FundsChange::credit(amount),
+                    t.currency().map(str::to_string),
+                )));
+            }
+
+            changes.push(
+                fee.and_then(FundsChange::debit)
+                    .map(|c| (c, currency.clone())),
+            );
+        }
+
+        if !self.apply_changes(tx, changes, messages) {
+            return;
+        }
+
+        self.close_cycle(tx.tx_id, index, tx.ty);
+
+        if tx.ty == TxType::Resolve {
+            self.restore(&clawed_back);
+        }
+
+        if tx.ty == TxType::Chargeback {
+            self.status = AccountStatus::Locked;
+            self.record_fee(tx, fee.unwrap_or_default(), currency.as_deref());
+        }
+    }
+
+    /// Finds the open dispute of the given amount, or the oldest one if the amount is not
+    /// given, that is closed by the resolve or chargeback `tx`. Returns index of the dispute
+    /// cycle and the disputed amount.
+    fn open_cycle(&self, tx: &Tx, messages: &mut Vec<Message>) -> Option<(usize, Value)> {
+        let t = match self.transactions.get(&tx.tx_id) {
+            Some(t) => t,
+            None => {
+                messages.push(Message::UnknownTransaction(tx.client_id, tx.tx_id));
+                return None;
+            }
+        };
+
+        if !self.check_currency(tx, t, messages) {
+            return None;
+        }
+
+        let index = t.disputes.iter().position(|c| {
+            c.outcome.is_none() && (!tx.amount.is_positive() || c.amount == tx.amount)
+        });
+
+        match index {
+            Some(index) => Some((index, t.disputes[index].amount)),
+            None => {
+                let message = match t.state {
+                    TxState::Active => Message::NotInDispute,
+                    TxState::InDispute => Message::DisputedAmountNotFound,
+                    TxState::Resolved => Message::AlreadyResolved,
+                    TxState::ChargedBack => Message::AlreadyChargedBack,
+                    TxState::ClawedBack => Message::AlreadyClawedBack,
+                };
+
+                messages.push(message(tx.client_id, tx.tx_id, t.ty));
+                None
+            }
+        }
+    }
+
+    /// Closes the dispute cycle found by `open_cycle` with resolve or chargeback `op`.
+    fn close_cycle(&mut self, tx_id: TxId, index: usize, op: TxType) {
+        if let Some(t) = self.transactions.get_mut(&tx_id) {
+            t.disputes[index].outcome = Some(op);
+            t.update_state();
+        }
+    }
+
+    /// Applies administrative operation, these are accepted on locked and frozen accounts.
+    fn admin(&mut self, tx: &Tx, config: &Config, messages: &mut Vec<Message>) {
+        if !config.admin_operations {
+            messages.push(Message::AdminNotAllowed(tx.client_id, tx.tx_id, tx.ty));
+            return;
+        }
+
+        self.status = match (tx.ty, self.status) {
+            (_, AccountStatus::Closed) => {
+                messages.push(Message::AccountIsClosed(tx.client_id, tx.tx_id, tx.ty));
+                return;
+            }
+            (TxType::Unlock, AccountStatus::Active) => {
+                messages.push(Message::AccountNotLocked(tx.client_id, tx.tx_id, tx.ty));
+                return;
+            }
+            (TxType::Unlock, _) => AccountStatus::Active,
+            (TxType::Freeze, _) => AccountStatus::Frozen,
+            (TxType::Close, _) => AccountStatus::Closed,
+            _ => unreachable!(),
+        };
+
+        self.operations.push(tx.clone());
+    }
+
+    /// Applies all `changes` of funds or none of them. Change that cannot be computed, or that
+    /// would overflow any balance, is reported and rejects the transaction.
+    fn apply_changes(
+        &mut self,
+        tx: &Tx,
+        changes: Vec<Option<Change>>,
+        messages: &mut Vec<Message>,
+    ) -> bool {
+        match self.changed_balances(changes) {
+            Some(balances) => {
+                self.set_balances(balances);
+                true
+            }
+            None => {
+                messages.push(Message::ValueOverflow(self.id, tx.tx_id, tx.ty));
+                false
+            }
+        }
     }
 
-    fn validate(&self, tx: &Tx, messages: &mut Vec<Message>) -> bool {
+    /// Balances after all `changes` of funds, `None` on overflow. Client is not changed until
+    /// the balances are set.
+    fn changed_balances(
+        &self,
+        changes: Vec<Option<Change>>,
+    ) -> Option<Vec<(Option<String>, Balance)>> {
+        let mut balances: Vec<(Option<String>, Balance)> = vec![];
+
+        for change in changes {
+            let (change, currency) = change?;
+            let currency = self.other_currency(currency.as_deref()).map(str::to_string);
+            let index = match balances.iter().position(|(c, _)| *c == currency) {
+                Some(index) => index,
+                None => {
+                    balances.push((currency.clone(), self.balance(currency.as_deref())));
+                    balances.len() - 1
+                }
+            };
+
+            balances[index].1 = balances[index].1.apply(change)?;
+        }
+
+        Some(balances)
+    }
+
+    fn set_balances(&mut self, balances: Vec<(Option<String>, Balance)>) {
+        for (currency, balance) in balances {
+            *self.balance_mut(currency.as_deref()) = balance;
+        }
+    }
+
+    /// Active withdrawals in the `currency` made after the transaction `tx_id`, latest first,
+    /// that have to be reversed until available funds cover the `amount`.
+    fn claw_back(&self, tx_id: TxId, amount: Value, currency: Option<&str>) -> Vec<(TxId, Value)> {
+        let currency = self.other_currency(currency);
+        let mut available = self.balance(currency).available;
+        let mut clawed_back = vec![];
+
+        for id in self.tx_order.iter().rev() {
+            if *id == tx_id || available >= amount {
+                break;
+            }
+
+            match self.transactions.get(id) {
+                Some(t)
+                    if t.ty == TxType::Withdrawal
+                        && t.state == TxState::Active
+                        && self.other_currency(t.currency()) == currency =>
+                {
+                    available = match available.checked_add(t.amount) {
+                        Some(available) => available,
+                        None => break,
+                    };
+                    clawed_back.push((t.tx_id, t.amount));
+                }
+                _ => (),
+            }
+        }
+
+        clawed_back
+    }
+
+    fn validate(&self, tx: &Tx, config: &Config, messages: &mut Vec<Message>) -> bool {
         assert_eq!(self.id, tx.client_id);
 
-        if self.locked {
-            messages.push(Message::AccountIsLocked(tx.client_id, tx.tx_id, tx.ty));
+        let debit = matches!(
+            tx.ty,
+            TxType::Withdrawal | TxType::Transfer | TxType::Exchange
+        );
+
+        if !self.check_status(tx, debit, config, messages) {
             return false;
         }
 
-        if (tx.ty == TxType::Deposit || tx.ty == TxType::Withdrawal)
-            && self.transactions.iter().any(|t| t.tx_id == tx.tx_id)
+        if matches!(
+            tx.ty,
+            TxType::Deposit | TxType::Withdrawal | TxType::Transfer | TxType::Exchange
+        ) && self.transactions.contains_key(&tx.tx_id)
         {
             messages.push(Message::TransactionExist(tx.client_id, tx.tx_id, tx.ty));
             return false;
@@ -151,4 +890,51 @@ impl Client {
 
         true
     }
+
+    /// Checks that account status allows the transaction, `debit` transaction takes funds
+    /// from the account.
+    fn check_status(
+        &self,
+        tx: &Tx,
+        debit: bool,
+        config: &Config,
+        messages: &mut Vec<Message>,
+    ) -> bool {
+        let blocked = match self.status {
+            AccountStatus::Active => false,
+            // Administrative operations report the status themselves
+            _ if tx.ty.is_admin() => false,
+            AccountStatus::Locked => match config.lock_policy {
+                LockPolicy::Full => true,
+                LockPolicy::DebitOnly => debit,
+                LockPolicy::None => false,
+            },
+            AccountStatus::Frozen | AccountStatus::Closed => true,
+        };
+
+        if blocked {
+            messages.push(match self.status {
+                AccountStatus::Closed => Message::AccountIsClosed(self.id, tx.tx_id, tx.ty),
+                _ => Message::AccountIsLocked(self.id, tx.tx_id, tx.ty),
+            });
+        }
+
+        !blocked
+    }
+}
+
+impl Balance {
+    /// Balance after the `change`, `None` if any of the funds overflows.
+    fn apply(self, change: FundsChange) -> Option<Self> {
+        Some(Self {
+            available: self.available.checked_add(change.available)?,
+            held: self.held.checked_add(change.held)?,
+            total: self.total.checked_add(change.total)?,
+        })
+    }
+}
+
+/// Transaction is accepted if processing reported no errors.
+fn accepted(messages: &[Message]) -> bool {
+    messages.iter().all(|m| m.severity() != Severity::Error)
 }
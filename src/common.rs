@@ -1,2 +1 @@
 pub type ClientId = u16;
-pub type Value = f32;
@@ -0,0 +1,79 @@
+use crate::{fee::FeeSchedule, rate::RateTable};
+use serde::Deserialize;
+
+/// What to do when a disputed deposit is larger than the available funds, e.g. because its
+/// funds were already withdrawn.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DisputePolicy {
+    /// Reject the dispute
+    Reject,
+    /// Hold the full amount and let available funds go negative
+    #[default]
+    Negative,
+    /// Reverse withdrawals made after the deposit, latest first, until available funds cover
+    /// the disputed amount
+    ClawBack,
+}
+
+/// How disputes of withdrawals change client funds.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WithdrawalDisputePolicy {
+    /// Reject disputes of withdrawals
+    Disallow,
+    /// Hold the withdrawn amount as a credit: dispute increases held and total funds, resolve
+    /// removes the credit and chargeback releases it to available funds
+    HoldCredit,
+    /// Dispute returns the withdrawn amount to available funds with negative held funds,
+    /// resolve takes it back and chargeback adds it to total funds
+    #[default]
+    Legacy,
+}
+
+/// Which transactions are rejected on the account locked by a chargeback. Frozen and closed
+/// accounts always reject all transactions except administrative operations.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LockPolicy {
+    /// Reject all transactions
+    #[default]
+    Full,
+    /// Reject withdrawals only, deposits are received and disputes can be settled
+    DebitOnly,
+    /// Accept all transactions, lock is only reported in the output
+    None,
+}
+
+/// Engine settings.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub dispute_policy: DisputePolicy,
+    pub withdrawal_dispute_policy: WithdrawalDisputePolicy,
+    pub lock_policy: LockPolicy,
+    /// Maximum number of times each part of a transaction amount can be disputed. Resolved
+    /// transaction can be disputed again until the limit is reached.
+    pub max_disputes: u32,
+    /// Accept administrative operations: unlock, freeze and close
+    pub admin_operations: bool,
+    pub fees: FeeSchedule,
+    /// Exchange rates between currencies
+    pub rates: RateTable,
+    /// Reject transactions of unknown clients, e.g. not registered by profiles
+    pub strict: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            dispute_policy: DisputePolicy::default(),
+            withdrawal_dispute_policy: WithdrawalDisputePolicy::default(),
+            lock_policy: LockPolicy::default(),
+            max_disputes: 1,
+            admin_operations: false,
+            fees: FeeSchedule::default(),
+            rates: RateTable::default(),
+            strict: false,
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use crate::{
+    common::ClientId,
+    message::{Message, Severity},
+    transaction::{TxId, TxType},
+};
+use anyhow::Result;
+use serde::Serialize;
+use std::io::Write;
+
+/// Machine-readable form of a `Message`.
+#[derive(Debug, Serialize)]
+pub struct Event {
+    pub code: &'static str,
+    pub severity: Severity,
+    pub client: ClientId,
+    pub tx: TxId,
+    pub tx_type: Option<TxType>,
+    pub message: String,
+}
+
+impl From<&Message> for Event {
+    fn from(message: &Message) -> Self {
+        Self {
+            code: message.code(),
+            severity: message.severity(),
+            client: message.client(),
+            tx: message.tx(),
+            tx_type: message.tx_type(),
+            message: message.text(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum EventFormat {
+    /// JSON object per line
+    #[default]
+    JsonLines,
+    Csv,
+}
+
+/// Writes messages as a stream of events.
+pub enum EventWriter<W: Write> {
+    JsonLines(W),
+    Csv(Box<csv::Writer<W>>),
+}
+
+impl<W: Write> EventWriter<W> {
+    pub fn new(writer: W, format: EventFormat) -> Self {
+        match format {
+            EventFormat::JsonLines => Self::JsonLines(writer),
+            EventFormat::Csv => Self::Csv(Box::new(csv::Writer::from_writer(writer))),
+        }
+    }
+
+    /// Continues events written by an interrupted run, CSV header is not written again.
+    pub fn resume(writer: W, format: EventFormat) -> Self {
+        match format {
+            EventFormat::JsonLines => Self::JsonLines(writer),
+            EventFormat::Csv => Self::Csv(Box::new(
+                csv::WriterBuilder::new()
+                    .has_headers(false)
+                    .from_writer(writer),
+            )),
+        }
+    }
+
+    pub fn write(&mut self, message: &Message) -> Result<()> {
+        let event = Event::from(message);
+
+        match self {
+            Self::JsonLines(writer) => {
+                serde_json::to_writer(&mut *writer, &event)?;
+                writer.write_all(b"\n")?;
+            }
+            Self::Csv(writer) => writer.serialize(&event)?,
+        }
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        match self {
+            Self::JsonLines(writer) => writer.flush()?,
+            Self::Csv(writer) => writer.flush()?,
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(format: EventFormat, messages: &[Message]) -> String {
+        let mut buf = vec![];
+        let mut writer = EventWriter::new(&mut buf, format);
+
+        for message in messages {
+            writer.write(message).unwrap();
+        }
+
+        writer.flush().unwrap();
+        drop(writer);
+
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn test_json_lines() {
+        assert_eq!(
+            "{\"code\":\"not_enough_funds\",\"severity\":\"error\",\"client\":1,\"tx\":2,\
+             \"tx_type\":\"withdrawal\",\"message\":\"Cannot process Withdrawal transaction 2 \
+             for client 1. Not enough funds.\"}\n\
+             {\"code\":\"unknown_transaction\",\"severity\":\"error\",\"client\":3,\"tx\":4,\
+             \"tx_type\":null,\"message\":\"Cannot process transaction 4 for client 3. \
+             Transaction is unknown.\"}\n",
+            write(
+                EventFormat::JsonLines,
+                &[
+                    Message::NotEnoughFunds(1, 2, TxType::Withdrawal),
+                    Message::UnknownTransaction(3, 4),
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn test_csv() {
+        assert_eq!(
+            "code,severity,client,tx,tx_type,message\n\
+             account_locked,error,1,2,deposit,Cannot process Deposit transaction 2 for client 1. \
+             Account is locked.\n\
+             unknown_transaction,error,3,4,,Cannot process transaction 4 for client 3. \
+             Transaction is unknown.\n",
+            write(
+                EventFormat::Csv,
+                &[
+                    Message::AccountIsLocked(1, 2, TxType::Deposit),
+                    Message::UnknownTransaction(3, 4),
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn test_csv_resume() {
+        let mut buf = vec![];
+        let mut writer = EventWriter::resume(&mut buf, EventFormat::Csv);
+
+        writer.write(&Message::UnknownTransaction(3, 4)).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+
+        assert_eq!(
+            "unknown_transaction,error,3,4,,Cannot process transaction 4 for client 3. \
+             Transaction is unknown.\n",
+            String::from_utf8(buf).unwrap()
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use crate::value::Value;
+use anyhow::{bail, Result};
+use serde::Deserialize;
+use std::io::Read;
+
+/// Fee charged for a transaction.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Fee {
+    /// Fixed amount
+    Flat(Value),
+    /// Percents of the transaction amount
+    Percentage(Value),
+    /// Fee of the tier with the largest `from` amount that doesn't exceed the transaction
+    /// amount, no fee below the first tier
+    Tiered(Vec<FeeTier>),
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct FeeTier {
+    pub from: Value,
+    pub fee: Fee,
+}
+
+impl Fee {
+    /// Fee for the transaction `amount`, `None` on overflow.
+    pub fn amount(&self, amount: Value) -> Option<Value> {
+        match self {
+            Self::Flat(fee) => Some(*fee),
+            Self::Percentage(percent) => amount.checked_percent(*percent),
+            Self::Tiered(tiers) => tiers
+                .iter()
+                .filter(|tier| tier.from <= amount)
+                .max_by_key(|tier| tier.from)
+                .map_or(Some(Value::ZERO), |tier| tier.fee.amount(amount)),
+        }
+    }
+
+    fn is_negative(&self) -> bool {
+        match self {
+            Self::Flat(value) | Self::Percentage(value) => *value < Value::ZERO,
+            Self::Tiered(tiers) => tiers.iter().any(|tier| tier.fee.is_negative()),
+        }
+    }
+}
+
+/// Fees charged by the engine. Fees are recorded in the client history as `fee` transactions
+/// that refer to the charged transaction.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FeeSchedule {
+    /// Charged on top of the withdrawn amount
+    #[serde(default)]
+    pub withdrawal: Option<Fee>,
+    /// Penalty charged from the account locked by a chargeback
+    #[serde(default)]
+    pub chargeback: Option<Fee>,
+}
+
+impl FeeSchedule {
+    /// Reads fee schedule from JSON.
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self> {
+        let schedule: Self = serde_json::from_reader(reader)?;
+
+        if [&schedule.withdrawal, &schedule.chargeback]
+            .iter()
+            .any(|fee| matches!(fee, Some(fee) if fee.is_negative()))
+        {
+            bail!("ERROR: Fees cannot be negative.");
+        }
+
+        Ok(schedule)
+    }
+
+    /// Fee for withdrawal of the `amount`, `None` on overflow.
+    pub fn withdrawal(&self, amount: Value) -> Option<Value> {
+        self.withdrawal
+            .as_ref()
+            .map_or(Some(Value::ZERO), |fee| fee.amount(amount))
+    }
+
+    /// Penalty for chargeback of the `amount`, `None` on overflow.
+    pub fn chargeback(&self, amount: Value) -> Option<Value> {
+        self.chargeback
+            .as_ref()
+            .map_or(Some(Value::ZERO), |fee| fee.amount(amount))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(value: &str) -> Value {
+        value.parse().unwrap()
+    }
+
+    fn schedule(json: &str) -> FeeSchedule {
+        FeeSchedule::from_reader(json.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_flat() {
+        let fees = schedule(r#"{"withdrawal": {"flat": "0.5"}}"#);
+
+        assert_eq!(Some(v("0.5")), fees.withdrawal(v("100")));
+        assert_eq!(Some(v("0")), fees.chargeback(v("100")));
+    }
+
+    #[test]
+    fn test_percentage() {
+        let fees = schedule(r#"{"withdrawal": {"percentage": "1.5"}}"#);
+
+        assert_eq!(Some(v("1.5")), fees.withdrawal(v("100")));
+        assert_eq!(Some(v("0.0002")), fees.withdrawal(v("0.01")));
+    }
+
+    #[test]
+    fn test_tiered() {
+        let fees = schedule(
+            r#"{"withdrawal": {"tiered": [
+                {"from": "1000", "fee": {"percentage": "0.1"}},
+                {"from": "10", "fee": {"flat": "1"}}
+            ]}}"#,
+        );
+
+        assert_eq!(Some(v("0")), fees.withdrawal(v("5")));
+        assert_eq!(Some(v("1")), fees.withdrawal(v("10")));
+        assert_eq!(Some(v("1")), fees.withdrawal(v("999.9999")));
+        assert_eq!(Some(v("2")), fees.withdrawal(v("2000")));
+    }
+
+    #[test]
+    fn test_negative_fail() {
+        assert!(FeeSchedule::from_reader(r#"{"chargeback": {"flat": "-1"}}"#.as_bytes()).is_err());
+        assert!(FeeSchedule::from_reader(
+            r#"{"withdrawal": {"tiered": [{"from": "0", "fee": {"percentage": "-1"}}]}}"#
+                .as_bytes()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_unknown_fee_fail() {
+        assert!(FeeSchedule::from_reader(r#"{"deposit": {"flat": "1"}}"#.as_bytes()).is_err());
+    }
+}
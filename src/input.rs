@@ -0,0 +1,429 @@
+use crate::{
+    common::ClientId,
+    profile::is_date,
+    transaction::{Tx, TxDetails, TxId, TxType},
+    value::Value,
+};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::{fs::File, io, path::Path};
+
+#[derive(Deserialize)]
+pub struct InputTx {
+    #[serde(rename = "type")]
+    pub ty: TxType,
+    pub client: ClientId,
+    pub tx: TxId,
+    pub amount: Option<Value>,
+    /// Client receiving the transfer
+    #[serde(default)]
+    pub destination: Option<ClientId>,
+    /// Reason code of the administrative operation
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// Currency code, e.g. `EUR`
+    #[serde(default)]
+    pub currency: Option<String>,
+    /// Currency the exchange converts funds to
+    #[serde(default)]
+    pub to_currency: Option<String>,
+    /// Date of the exchange in `YYYY-MM-DD` format
+    #[serde(default)]
+    pub date: Option<String>,
+}
+
+impl From<InputTx> for Tx {
+    fn from(tx: InputTx) -> Self {
+        let (reason, currency) = if tx.ty.is_admin() {
+            (tx.reason, None)
+        } else {
+            (None, tx.currency)
+        };
+        let destination = if tx.ty == TxType::Transfer {
+            tx.destination
+        } else {
+            None
+        };
+        let (to_currency, date) = if tx.ty == TxType::Exchange {
+            (tx.to_currency, tx.date)
+        } else {
+            (None, None)
+        };
+
+        Self::new(tx.ty, tx.client, tx.tx, tx.amount.unwrap_or(Value::ZERO)).with_details(
+            TxDetails {
+                destination,
+                reason,
+                currency,
+                to_currency,
+                date,
+                rate: None,
+            },
+        )
+    }
+}
+
+impl InputTx {
+    pub fn validate(&self) -> Result<()> {
+        // Amount of dispute, resolve and chargeback is optional and selects the disputed part
+        match (self.ty, self.amount) {
+            (_, Some(amount)) if !amount.is_positive() => {
+                bail!(
+                    "{:?} transaction {} for client {} contains negative amount.",
+                    self.ty,
+                    self.tx,
+                    self.client
+                );
+            }
+            (TxType::Fee, _) => {
+                bail!(
+                    "{:?} transaction {} for client {} is reserved for the system.",
+                    self.ty,
+                    self.tx,
+                    self.client
+                );
+            }
+            (ty, Some(_)) if ty.is_admin() => {
+                bail!(
+                    "{:?} transaction {} for client {} should not contain amount.",
+                    self.ty,
+                    self.tx,
+                    self.client
+                );
+            }
+            (ty, _) if ty.is_admin() && self.reason.is_none() => {
+                bail!(
+                    "{:?} transaction {} for client {} contains no reason.",
+                    self.ty,
+                    self.tx,
+                    self.client
+                );
+            }
+            (TxType::Deposit | TxType::Withdrawal | TxType::Transfer | TxType::Exchange, None) => {
+                bail!(
+                    "{:?} transaction {} for client {} contains no amount.",
+                    self.ty,
+                    self.tx,
+                    self.client
+                );
+            }
+            (TxType::Transfer, _) if self.destination.is_none() => {
+                bail!(
+                    "{:?} transaction {} for client {} contains no destination.",
+                    self.ty,
+                    self.tx,
+                    self.client
+                );
+            }
+            _ if !self.currency.as_deref().is_none_or(is_currency) => {
+                bail!(
+                    "{:?} transaction {} for client {} contains invalid currency.",
+                    self.ty,
+                    self.tx,
+                    self.client
+                );
+            }
+            (TxType::Exchange, _)
+                if !self.to_currency.as_deref().is_some_and(is_currency)
+                    || self.to_currency == self.currency =>
+            {
+                bail!(
+                    "{:?} transaction {} for client {} contains invalid target currency.",
+                    self.ty,
+                    self.tx,
+                    self.client
+                );
+            }
+            (TxType::Exchange, _) if !self.date.as_deref().is_none_or(is_date) => {
+                bail!(
+                    "{:?} transaction {} for client {} contains invalid date.",
+                    self.ty,
+                    self.tx,
+                    self.client
+                );
+            }
+            (TxType::Transfer, _) if self.destination == Some(self.client) => {
+                bail!(
+                    "{:?} transaction {} for client {} transfers funds to the same client.",
+                    self.ty,
+                    self.tx,
+                    self.client
+                );
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+/// Checks that the value is a currency code of three uppercase letters, e.g. `USD`.
+pub(crate) fn is_currency(value: &str) -> bool {
+    value.len() == 3 && value.bytes().all(|b| b.is_ascii_uppercase())
+}
+
+/// Input record that cannot be turned into a transaction.
+#[derive(Serialize, PartialEq)]
+pub struct Rejection {
+    /// Line number of the record in the input file
+    pub line: u64,
+    /// Record fields as they appear in the input
+    pub record: String,
+    pub reason: String,
+}
+
+impl std::fmt::Debug for Rejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "ERROR: Rejected record '{}' at line {}. {}",
+            self.record, self.line, self.reason
+        ))
+    }
+}
+
+/// Reads transactions from CSV input one record at a time.
+pub struct TxReader<R> {
+    reader: csv::Reader<R>,
+    headers: csv::StringRecord,
+    record: csv::ByteRecord,
+}
+
+impl TxReader<File> {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::new(File::open(path)?)
+    }
+}
+
+impl<R: io::Read> TxReader<R> {
+    pub fn new(input: R) -> Result<Self> {
+        let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(input);
+        let mut headers = reader.headers()?.clone();
+
+        headers.trim();
+
+        Ok(Self {
+            reader,
+            headers,
+            record: csv::ByteRecord::new(),
+        })
+    }
+
+    /// Line number of the last read record in the input.
+    pub fn line(&self) -> u64 {
+        self.record.position().map_or(0, |p| p.line())
+    }
+
+    /// Returns `None` at the end of input. Records that cannot be decoded, parsed or validated
+    /// are returned as rejections, only I/O errors are fatal.
+    pub fn read(&mut self) -> Result<Option<Result<Tx, Rejection>>> {
+        if !self.reader.read_byte_record(&mut self.record)? {
+            return Ok(None);
+        }
+
+        let raw = raw_record(&self.record)?;
+        let line = self.line();
+
+        let result = csv::StringRecord::from_byte_record(self.record.clone())
+            .map_err(|_| anyhow::anyhow!("Record contains invalid UTF-8."))
+            .and_then(|mut record| {
+                record.trim();
+                Ok(record.deserialize::<InputTx>(Some(&self.headers))?)
+            })
+            .and_then(|input_tx| {
+                input_tx.validate()?;
+                Ok(input_tx.into())
+            })
+            .map_err(|e| Rejection {
+                line,
+                record: raw,
+                reason: e.to_string(),
+            });
+
+        Ok(Some(result))
+    }
+}
+
+/// Encodes record back to a CSV line so it can be replayed as is. Invalid UTF-8 sequences
+/// are replaced with the replacement character.
+fn raw_record(record: &csv::ByteRecord) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+
+    writer.write_record(record)?;
+
+    let line = String::from_utf8_lossy(&writer.into_inner()?).into_owned();
+
+    Ok(line.trim_end().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_all(input: &str) -> Vec<Result<TxId, u64>> {
+        let mut reader = TxReader::new(input.as_bytes()).unwrap();
+        let mut result = vec![];
+
+        while let Some(record) = reader.read().unwrap() {
+            result.push(record.map(|tx| tx.tx_id).map_err(|r| r.line));
+        }
+
+        result
+    }
+
+    #[test]
+    fn test_read() {
+        assert_eq!(
+            vec![Ok(1), Ok(2), Ok(1)],
+            read_all(
+                "type, client, tx, amount\n\
+                 deposit, 1, 1, 1.0\n\
+                 withdrawal, 1, 2, 0.5\n\
+                 dispute, 1, 1,\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_read_transfer() {
+        let mut reader = TxReader::new(
+            "type,client,tx,amount,destination\n\
+             transfer,1,1,1.0,2\n\
+             transfer,1,2,1.0,1\n\
+             deposit,1,3,1.0,2\n"
+                .as_bytes(),
+        )
+        .unwrap();
+
+        let tx = reader.read().unwrap().unwrap().unwrap();
+
+        assert_eq!(TxType::Transfer, tx.ty);
+        assert_eq!(Some(2), tx.destination());
+        assert!(reader.read().unwrap().unwrap().is_err());
+        assert_eq!(None, reader.read().unwrap().unwrap().unwrap().destination());
+    }
+
+    #[test]
+    fn test_read_admin() {
+        let mut reader = TxReader::new(
+            "type,client,tx,amount,reason\n\
+             freeze,1,1,,fraud\n\
+             unlock,1,2,,\n\
+             close,1,3,1.0,fraud\n\
+             deposit,1,4,1.0,bonus\n"
+                .as_bytes(),
+        )
+        .unwrap();
+
+        let tx = reader.read().unwrap().unwrap().unwrap();
+
+        assert_eq!(TxType::Freeze, tx.ty);
+        assert_eq!(Some("fraud"), tx.reason());
+        assert!(reader.read().unwrap().unwrap().is_err());
+        assert!(reader.read().unwrap().unwrap().is_err());
+        assert_eq!(None, reader.read().unwrap().unwrap().unwrap().reason());
+    }
+
+    #[test]
+    fn test_read_currency() {
+        let mut reader = TxReader::new(
+            "type,client,tx,amount,currency\n\
+             deposit,1,1,1.0,EUR\n\
+             deposit,1,2,1.0,\n\
+             deposit,1,3,1.0,eur\n\
+             deposit,1,4,1.0,EURO\n"
+                .as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            Some("EUR"),
+            reader.read().unwrap().unwrap().unwrap().currency()
+        );
+        assert_eq!(None, reader.read().unwrap().unwrap().unwrap().currency());
+        assert!(reader.read().unwrap().unwrap().is_err());
+        assert!(reader.read().unwrap().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_read_exchange() {
+        let mut reader = TxReader::new(
+            "type,client,tx,amount,currency,to_currency,date\n\
+             exchange,1,1,1.0,EUR,USD,2021-01-01\n\
+             exchange,1,2,1.0,,USD,\n\
+             exchange,1,3,1.0,EUR,EUR,\n\
+             exchange,1,4,1.0,EUR,,\n\
+             exchange,1,5,1.0,EUR,USD,01.01.2021\n\
+             deposit,1,6,1.0,EUR,USD,2021-01-01\n"
+                .as_bytes(),
+        )
+        .unwrap();
+
+        let tx = reader.read().unwrap().unwrap().unwrap();
+
+        assert_eq!(TxType::Exchange, tx.ty);
+        assert_eq!(Some("USD"), tx.to_currency());
+        assert_eq!(Some("2021-01-01"), tx.date());
+        assert!(reader.read().unwrap().unwrap().is_ok());
+
+        for _ in 0..3 {
+            assert!(reader.read().unwrap().unwrap().is_err());
+        }
+
+        let tx = reader.read().unwrap().unwrap().unwrap();
+
+        assert_eq!((None, None), (tx.to_currency(), tx.date()));
+    }
+
+    #[test]
+    fn test_read_dispute_amount() {
+        let mut reader =
+            TxReader::new("type,client,tx,amount\ndispute,1,1,0.5\nresolve,1,1,-0.5\n".as_bytes())
+                .unwrap();
+
+        let tx = reader.read().unwrap().unwrap().unwrap();
+
+        assert_eq!(TxType::Dispute, tx.ty);
+        assert_eq!("0.5000", tx.amount.to_string());
+        assert!(reader.read().unwrap().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_read_rejected() {
+        assert_eq!(
+            vec![Err(2), Ok(2), Err(4), Err(5), Err(6), Ok(4)],
+            read_all(
+                "type, client, tx, amount\n\
+                 deposit, 1, 1, abc\n\
+                 deposit, 1, 2, 1.0\n\
+                 withdrawal, 1, 3\n\
+                 deposit, 1, 4, -1.0\n\
+                 transfer, 1, 5, 1.0\n\
+                 deposit, 1, 4, 1.0\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_rejection_record() {
+        let mut reader =
+            TxReader::new("type,client,tx,amount\ndeposit,\"1,2\",1,1.0\n".as_bytes()).unwrap();
+        let rejection = reader.read().unwrap().unwrap().unwrap_err();
+
+        assert_eq!(2, rejection.line);
+        assert_eq!("deposit,\"1,2\",1,1.0", rejection.record);
+    }
+
+    #[test]
+    fn test_rejection_invalid_utf8() {
+        let mut input = b"type,client,tx,amount\ndeposit,1,1,".to_vec();
+
+        input.extend_from_slice(b"\xff\xfe\ndeposit,1,2,1.0\n");
+
+        let mut reader = TxReader::new(input.as_slice()).unwrap();
+        let rejection = reader.read().unwrap().unwrap().unwrap_err();
+
+        assert_eq!(2, rejection.line);
+        assert_eq!("deposit,1,1,\u{fffd}\u{fffd}", rejection.record);
+        assert_eq!("Record contains invalid UTF-8.", rejection.reason);
+        assert_eq!(2, reader.read().unwrap().unwrap().unwrap().tx_id);
+    }
+}
@@ -0,0 +1,294 @@
+use crate::{client::ClientSnapshot, process::Engine, transaction::Tx};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+/// Processed transaction and the resulting state of its client.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    /// Line of the transaction in the input file
+    line: u64,
+    /// Sequence number of the transaction in the engine
+    seq: u64,
+    /// `None` for input record rejected before it reached the engine
+    tx: Option<Tx>,
+    accepted: bool,
+    /// Client state, `None` if the client is not known to the engine
+    client: Option<ClientSnapshot>,
+}
+
+/// Write-ahead journal of processed transactions.
+///
+/// Every entry is written as a JSON line and synced to disk before the transaction is
+/// acknowledged. Rejected transactions are journaled too, as they reserve transaction ids and
+/// register their clients, and so are rejected input records, so they are not reported twice
+/// when the input is read again. After a crash the journal is replayed on top of the last saved
+/// state so the input can be processed again starting after the last applied line. Entries
+/// already included in the state, e.g. when the crash happened after the state was saved but
+/// before the journal was cleared, are skipped by their sequence number.
+pub struct Journal {
+    file: File,
+}
+
+impl Journal {
+    /// Opens or creates journal and replays its entries on top of `engine`. Returns journal
+    /// and the input line of the last applied transaction, `0` if journal is empty.
+    pub fn open<P: AsRef<Path>>(path: P, engine: &mut Engine) -> Result<(Self, u64)> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let mut reader = BufReader::new(&mut file);
+        let mut buf = String::new();
+        let mut messages = vec![];
+        let mut valid_len = 0;
+        let mut last_line = 0;
+
+        while reader.read_line(&mut buf)? > 0 {
+            // Entry without line end was not completely written before the crash
+            if !buf.ends_with('\n') {
+                break;
+            }
+
+            let entry: Entry = serde_json::from_str(&buf)?;
+
+            let mismatch = match &entry.tx {
+                Some(tx) if entry.seq > engine.seq() => {
+                    engine.process(tx, &mut messages) != entry.accepted
+                        || engine.seq() != entry.seq
+                        || engine.client(tx.client_id) != entry.client
+                }
+                _ => false,
+            };
+
+            if mismatch {
+                bail!(
+                    "ERROR: Journal entry for line {} doesn't match engine state.",
+                    entry.line
+                );
+            }
+
+            valid_len += buf.len() as u64;
+            last_line = entry.line;
+            buf.clear();
+        }
+
+        file.set_len(valid_len)?;
+        file.seek(SeekFrom::End(0))?;
+
+        Ok((Self { file }, last_line))
+    }
+
+    /// Records transaction just processed by the `engine` with the resulting client state.
+    pub fn append(&mut self, line: u64, tx: &Tx, accepted: bool, engine: &Engine) -> Result<()> {
+        self.write(&Entry {
+            line,
+            seq: engine.seq(),
+            tx: Some(tx.clone()),
+            accepted,
+            client: engine.client(tx.client_id),
+        })
+    }
+
+    /// Records input line that could not be turned into a transaction.
+    pub fn append_rejection(&mut self, line: u64, engine: &Engine) -> Result<()> {
+        self.write(&Entry {
+            line,
+            seq: engine.seq(),
+            tx: None,
+            accepted: false,
+            client: None,
+        })
+    }
+
+    fn write(&mut self, entry: &Entry) -> Result<()> {
+        let mut buf = serde_json::to_vec(entry)?;
+
+        buf.push(b'\n');
+        self.file.write_all(&buf)?;
+        self.file.sync_data()?;
+
+        Ok(())
+    }
+
+    /// Removes all entries, used once their effect is saved in the engine state.
+    pub fn clear(&mut self) -> Result<()> {
+        self.file.set_len(0)?;
+        self.file.seek(SeekFrom::Start(0))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, message::Message, transaction::TxType, value::Value};
+    use std::{env, fs};
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        env::temp_dir().join(format!("journal_{}_{}", std::process::id(), name))
+    }
+
+    fn write(path: &std::path::Path, engine: &mut Engine, transactions: &[Tx]) {
+        let (mut journal, _) = Journal::open(path, engine).unwrap();
+
+        for (line, tx) in transactions.iter().enumerate() {
+            let accepted = engine.process(tx, &mut vec![]);
+
+            journal
+                .append(line as u64 + 2, tx, accepted, engine)
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_replay() {
+        let path = temp_path("replay");
+        let amount = Value::from_units(10_000);
+        let transactions = [
+            Tx::new(TxType::Deposit, 1, 1, amount),
+            Tx::new(TxType::Withdrawal, 1, 2, amount + amount),
+            Tx::new(TxType::Deposit, 2, 3, amount),
+            Tx::new(TxType::Dispute, 1, 1, Value::ZERO),
+        ];
+
+        write(&path, &mut Engine::new(), &transactions);
+
+        // Torn write of the last entry
+        let mut content = fs::read_to_string(&path).unwrap();
+
+        content.push_str(r#"{"line":6,"tx""#);
+        fs::write(&path, content).unwrap();
+
+        let mut engine = Engine::new();
+        let (mut journal, last_line) = Journal::open(&path, &mut engine).unwrap();
+
+        assert_eq!(5, last_line);
+        assert_eq!(amount, engine.client(1).unwrap().held);
+        assert_eq!(amount, engine.client(2).unwrap().available);
+
+        journal.clear().unwrap();
+
+        let (_, last_line) = Journal::open(&path, &mut Engine::new()).unwrap();
+
+        assert_eq!(0, last_line);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_replay_rejected() {
+        let path = temp_path("rejected");
+        let amount = Value::from_units(10_000);
+
+        write(
+            &path,
+            &mut Engine::new(),
+            &[
+                Tx::new(TxType::Withdrawal, 1, 7, amount),
+                Tx::new(TxType::Deposit, 3, 8, amount),
+            ],
+        );
+
+        let mut engine = Engine::new();
+        let mut messages = vec![];
+
+        Journal::open(&path, &mut engine).unwrap();
+        engine.process(&Tx::new(TxType::Deposit, 2, 7, amount), &mut messages);
+
+        // Rejected withdrawal still reserves its id and registers the client
+        assert_eq!(
+            vec![Message::TransactionExistForOtherClient(
+                2,
+                7,
+                TxType::Deposit
+            )],
+            messages
+        );
+        assert_eq!(Value::ZERO, engine.client(1).unwrap().available);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_replay_rejected_record() {
+        let path = temp_path("record");
+        let mut engine = Engine::new();
+
+        write(
+            &path,
+            &mut engine,
+            &[Tx::new(TxType::Deposit, 1, 1, Value::from_units(10_000))],
+        );
+
+        let (mut journal, _) = Journal::open(&path, &mut Engine::new()).unwrap();
+
+        journal.append_rejection(3, &engine).unwrap();
+
+        let mut engine = Engine::new();
+        let (_, last_line) = Journal::open(&path, &mut engine).unwrap();
+
+        assert_eq!(3, last_line);
+        assert_eq!(1, engine.seq());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_replay_saved_state() {
+        let path = temp_path("saved");
+        let amount = Value::from_units(10_000);
+        let mut engine = Engine::new();
+
+        write(
+            &path,
+            &mut engine,
+            &[
+                Tx::new(TxType::Deposit, 1, 1, amount),
+                Tx::new(TxType::Deposit, 1, 2, amount),
+            ],
+        );
+
+        // State was saved but the journal wasn't cleared before the crash
+        let mut state = vec![];
+
+        engine.save_state(&mut state).unwrap();
+
+        let mut engine = Engine::load_state(state.as_slice()).unwrap();
+        let (_, last_line) = Journal::open(&path, &mut engine).unwrap();
+
+        assert_eq!(3, last_line);
+        assert_eq!(2, engine.seq());
+        assert_eq!(amount + amount, engine.client(1).unwrap().available);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_replay_mismatch_fail() {
+        let path = temp_path("mismatch");
+
+        write(
+            &path,
+            &mut Engine::new(),
+            &[Tx::new(TxType::Deposit, 1, 1, Value::from_units(10_000))],
+        );
+
+        // Strict engine rejects the journaled deposit of an unknown client
+        let mut engine = Engine::with_config(Config {
+            strict: true,
+            ..Config::default()
+        });
+
+        assert!(Journal::open(&path, &mut engine).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
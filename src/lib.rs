@@ -0,0 +1,37 @@
+//! Transactions engine that processes deposits, withdrawals and disputes and keeps track of
+//! client accounts.
+
+mod client;
+mod common;
+mod config;
+mod event;
+mod fee;
+mod input;
+mod journal;
+mod message;
+mod parallel;
+mod process;
+mod profile;
+mod rate;
+mod registry;
+mod state;
+mod transaction;
+mod value;
+
+pub use crate::{
+    client::{AccountStatus, ClientSnapshot},
+    common::ClientId,
+    config::{Config, DisputePolicy, LockPolicy, WithdrawalDisputePolicy},
+    event::{Event, EventFormat, EventWriter},
+    fee::{Fee, FeeSchedule, FeeTier},
+    input::{InputTx, Rejection, TxReader},
+    journal::Journal,
+    message::{Message, Severity},
+    parallel::ParallelEngine,
+    process::{process, Engine, SortOrder},
+    profile::Profile,
+    rate::{Rate, RateTable},
+    state::STATE_VERSION,
+    transaction::{DisputeCycle, FundsChange, Tx, TxDetails, TxId, TxState, TxType},
+    value::{ParseValueError, Value},
+};
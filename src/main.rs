@@ -1,122 +1,429 @@
-use crate::{
-    common::{ClientId, Value},
-    process::process,
-    transaction::{Tx, TxId, TxState, TxType},
+use crate::args::{Args, OutputFormat};
+use anyhow::Result;
+use std::{
+    env,
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, BufWriter, Write},
+};
+use transactions_test::{
+    ClientSnapshot, Engine, EventWriter, FeeSchedule, Journal, Message, ParallelEngine, Profile,
+    RateTable, Tx, TxReader, Value,
 };
-use anyhow::{bail, Result};
-use client::Client;
-use serde::Deserialize;
-use std::{env, io};
 
-mod client;
-mod common;
-mod message;
-mod process;
-mod transaction;
+mod args;
 
 fn main() -> Result<()> {
-    let args: Vec<String> = env::args().collect();
+    let args = Args::parse(env::args().skip(1))?;
+    let mut engine = match &args.state {
+        Some(path) => Engine::load_state(BufReader::new(File::open(path)?))?,
+        None => Engine::new(),
+    };
+
+    let mut config = args.config.clone();
+
+    if let Some(path) = &args.fees {
+        config.fees = FeeSchedule::from_reader(BufReader::new(File::open(path)?))?;
+    }
+
+    if let Some(path) = &args.rates {
+        config.rates = RateTable::read_all(BufReader::new(File::open(path)?))?;
+    }
+
+    engine.set_config(config);
 
-    if args.len() < 2 {
-        bail!("ERROR: Expected CSV file as input parameter.");
+    if let Some(path) = &args.profiles {
+        let reader = BufReader::new(File::open(path)?);
+        let profiles = if path.ends_with(".json") {
+            Profile::read_json(reader)?
+        } else {
+            Profile::read_all(reader)?
+        };
+
+        for profile in profiles {
+            engine.set_profile(&profile)?;
+        }
     }
 
-    let transactions = load_transactions(&args[1])?;
+    let (mut journal, last_line) = match &args.journal {
+        Some(path) => {
+            let (journal, last_line) = Journal::open(path, &mut engine)?;
+            (Some(journal), last_line)
+        }
+        None => (None, 0),
+    };
+    let mut reporter = Reporter::new(&args, last_line)?;
+    let engine = process_transactions(&args, engine, journal.as_mut(), last_line, &mut reporter)?;
 
-    // transactions.iter().for_each(|tx| eprintln!("{:?}", tx));
+    reporter.flush()?;
 
-    let mut messages = vec![];
-    let clients = process(&transactions, &mut messages);
+    if let Some(path) = &args.save_state {
+        save_state(&engine, path)?;
 
-    for m in messages {
-        eprintln!("{:?}", m);
+        if let Some(journal) = journal.as_mut() {
+            journal.clear()?;
+        }
     }
 
+    let clients = engine.sorted_clients(args.sort);
+
     // println!("Clients: {:#?}", clients);
 
-    print_clients(clients)
+    let stdout = io::stdout();
+
+    write_clients(BufWriter::new(stdout.lock()), clients, args.output_format)
 }
 
-fn load_transactions(path: &str) -> Result<Vec<Tx>> {
-    let mut reader = csv::ReaderBuilder::new()
-        .trim(csv::Trim::All)
-        .flexible(true)
-        .from_path(path)?;
-    let mut transactions = vec![];
+/// Processes transactions on the current thread, or on several threads if requested.
+/// Processed transactions are recorded in the journal before the next one is read.
+fn process_transactions(
+    args: &Args,
+    mut engine: Engine,
+    mut journal: Option<&mut Journal>,
+    last_line: u64,
+    reporter: &mut Reporter,
+) -> Result<Engine> {
+    if args.threads > 1 {
+        let mut engine = ParallelEngine::with_engine(engine, args.threads);
+
+        read_transactions(args, last_line, |tx, _| {
+            if let Some(tx) = tx {
+                engine.process(&tx);
+            }
 
-    for record in reader.deserialize() {
-        let input_tx: InputTx = record?;
+            Ok(())
+        })?;
 
-        input_tx.validate()?;
+        let (engine, messages) = engine.into_engine();
 
-        transactions.push(input_tx.into());
-    }
+        for m in messages {
+            reporter.report(&m)?;
+        }
+
+        Ok(engine)
+    } else {
+        let mut messages = vec![];
+
+        read_transactions(args, last_line, |tx, line| {
+            let tx = match tx {
+                Some(tx) => tx,
+                None => {
+                    if let Some(journal) = journal.as_mut() {
+                        journal.append_rejection(line, &engine)?;
+                    }
+
+                    return Ok(());
+                }
+            };
+            let accepted = engine.process(&tx, &mut messages);
+
+            for m in messages.drain(..) {
+                reporter.report(&m)?;
+            }
 
-    Ok(transactions)
+            if let Some(journal) = journal.as_mut() {
+                // Journaled transaction must not lose its events after a crash
+                reporter.flush()?;
+                journal.append(line, &tx, accepted, &engine)?;
+            }
+
+            Ok(())
+        })?;
+
+        Ok(engine)
+    }
 }
 
-fn print_clients(clients: Vec<Client>) -> Result<()> {
-    let mut writer = csv::Writer::from_writer(io::stdout());
+/// Reads transactions from the CSV file one record at a time and passes them with their
+/// line number to `process`. Records up to `last_line` were processed by the previous run and
+/// are skipped. Records that cannot be turned into a transaction are reported and passed as
+/// `None`, so they can be journaled.
+fn read_transactions<F>(args: &Args, last_line: u64, mut process: F) -> Result<()>
+where
+    F: FnMut(Option<Tx>, u64) -> Result<()>,
+{
+    let mut reader = TxReader::from_path(&args.input)?;
+    let mut rejects = match &args.rejects {
+        // Continue rejects of the interrupted run
+        Some(path) if last_line > 0 => Some(
+            csv::WriterBuilder::new()
+                .has_headers(false)
+                .from_writer(OpenOptions::new().append(true).create(true).open(path)?),
+        ),
+        Some(path) => Some(csv::Writer::from_writer(File::create(path)?)),
+        None => None,
+    };
 
-    for client in clients {
-        writer.serialize(client)?;
+    while let Some(result) = reader.read()? {
+        if reader.line() <= last_line {
+            continue;
+        }
+
+        match result {
+            Ok(tx) => {
+                // eprintln!("{:?}", tx);
+
+                process(Some(tx), reader.line())?;
+            }
+            Err(rejection) => {
+                eprintln!("{:?}", rejection);
+
+                if let Some(writer) = rejects.as_mut() {
+                    writer.serialize(&rejection)?;
+
+                    // Journaled rejection must be on disk, it is not read again after a crash
+                    if args.journal.is_some() {
+                        writer.flush()?;
+                    }
+                }
+
+                process(None, rejection.line)?;
+            }
+        }
+    }
+
+    if let Some(writer) = rejects.as_mut() {
+        writer.flush()?;
     }
 
     Ok(())
 }
 
-#[derive(Deserialize)]
-pub struct InputTx {
-    #[serde(rename = "type")]
-    pub ty: TxType,
-    pub client: ClientId,
-    pub tx: TxId,
-    pub amount: Option<Value>,
+/// Reports engine messages to the stderr and to the optional events file.
+struct Reporter {
+    events: Option<EventWriter<BufWriter<File>>>,
 }
 
-impl From<InputTx> for Tx {
-    fn from(tx: InputTx) -> Self {
-        Self {
-            ty: tx.ty,
-            client_id: tx.client,
-            tx_id: tx.tx,
-            amount: tx.amount.unwrap_or_default(),
-            state: TxState::Active,
+impl Reporter {
+    /// Events of the interrupted run are kept when the journal was replayed up to `last_line`.
+    fn new(args: &Args, last_line: u64) -> Result<Self> {
+        let events = match &args.events {
+            Some(path) if last_line > 0 => Some(EventWriter::resume(
+                BufWriter::new(OpenOptions::new().append(true).create(true).open(path)?),
+                args.events_format,
+            )),
+            Some(path) => Some(EventWriter::new(
+                BufWriter::new(File::create(path)?),
+                args.events_format,
+            )),
+            None => None,
+        };
+
+        Ok(Self { events })
+    }
+
+    fn report(&mut self, message: &Message) -> Result<()> {
+        eprintln!("{:?}", message);
+
+        if let Some(events) = self.events.as_mut() {
+            events.write(message)?;
         }
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if let Some(events) = self.events.as_mut() {
+            events.flush()?;
+        }
+
+        Ok(())
     }
 }
 
-impl InputTx {
-    fn validate(&self) -> Result<()> {
-        match self.ty {
-            TxType::Deposit | TxType::Withdrawal => match self.amount {
-                Some(amount) if amount <= 0.0 => {
-                    bail!(
-                        "ERROR: {:?} transaction {} for client {} contains negative amount.",
-                        self.ty,
-                        self.tx,
-                        self.client
-                    );
-                }
-                None => {
-                    bail!(
-                        "ERROR: {:?} transaction {} for client {} contains no amount.",
-                        self.ty,
-                        self.tx,
-                        self.client
-                    );
-                }
-                _ => (),
-            },
-            _ => {
-                if self.amount.is_some() {
-                    eprintln!(
-                        "WARNING: {:?} transaction {} for client {} should not contain amount.",
-                        self.ty, self.tx, self.client
-                    );
-                }
+/// Writes state to a temporary file first so a failure never leaves a truncated state.
+fn save_state(engine: &Engine, path: &str) -> Result<()> {
+    let tmp_path = format!("{}.tmp", path);
+    let mut writer = BufWriter::new(File::create(&tmp_path)?);
+
+    engine.save_state(&mut writer)?;
+    writer.flush()?;
+    drop(writer);
+
+    fs::rename(tmp_path, path)?;
+
+    Ok(())
+}
+
+/// Fills optional columns so all rows have the same columns when any client has them.
+/// Clients without a credit limit get zero remaining credit, clients in the implicit
+/// currency get an empty currency.
+fn fill_columns(clients: &mut [ClientSnapshot]) {
+    if clients.iter().any(|c| c.credit.is_some()) {
+        for client in clients.iter_mut() {
+            client.credit.get_or_insert(Value::ZERO);
+        }
+    }
+
+    if clients.iter().any(|c| c.currency.is_some()) {
+        for client in clients.iter_mut() {
+            client.currency.get_or_insert_with(String::new);
+        }
+    }
+}
+
+/// Writes client balances in the output `format`, all rows have the same columns.
+fn write_clients<W: Write>(
+    mut out: W,
+    mut clients: Vec<ClientSnapshot>,
+    format: OutputFormat,
+) -> Result<()> {
+    fill_columns(&mut clients);
+
+    match format {
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(&mut out);
+
+            for client in clients {
+                writer.serialize(client)?;
             }
+
+            writer.flush()?;
         }
-        Ok(())
+        OutputFormat::Json => {
+            serde_json::to_writer(&mut out, &clients)?;
+            writeln!(out)?;
+        }
+        OutputFormat::JsonLines => {
+            for client in clients {
+                serde_json::to_writer(&mut out, &client)?;
+                writeln!(out)?;
+            }
+        }
+    }
+
+    out.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(id: u16, currency: Option<&str>, credit: Option<Value>) -> ClientSnapshot {
+        ClientSnapshot {
+            id,
+            currency: currency.map(String::from),
+            available: Value::ZERO,
+            held: Value::ZERO,
+            total: Value::ZERO,
+            locked: false,
+            credit,
+        }
+    }
+
+    fn funded(id: u16, available: &str, held: &str, locked: bool) -> ClientSnapshot {
+        let available: Value = available.parse().unwrap();
+        let held: Value = held.parse().unwrap();
+
+        ClientSnapshot {
+            available,
+            held,
+            total: available.checked_add(held).unwrap(),
+            locked,
+            ..client(id, None, None)
+        }
+    }
+
+    fn write(format: OutputFormat, clients: Vec<ClientSnapshot>) -> String {
+        let mut buf = vec![];
+
+        write_clients(&mut buf, clients, format).unwrap();
+
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn test_fill_columns() {
+        let credit = Value::from_units(10_000);
+        let mut clients = vec![client(1, None, None), client(2, None, Some(credit))];
+
+        fill_columns(&mut clients);
+
+        assert_eq!(
+            vec![
+                client(1, None, Some(Value::ZERO)),
+                client(2, None, Some(credit))
+            ],
+            clients
+        );
+
+        let mut clients = vec![client(1, Some("EUR"), None), client(2, None, None)];
+
+        fill_columns(&mut clients);
+
+        assert_eq!(
+            vec![client(1, Some("EUR"), None), client(2, Some(""), None)],
+            clients
+        );
+
+        let mut clients = vec![client(1, None, None)];
+
+        fill_columns(&mut clients);
+
+        assert_eq!(vec![client(1, None, None)], clients);
+    }
+
+    #[test]
+    fn test_write_csv() {
+        assert_eq!(
+            "client,available,held,total,locked\n\
+             1,1.5000,0.2500,1.7500,false\n\
+             2,-0.0001,0.0000,-0.0001,true\n",
+            write(
+                OutputFormat::Csv,
+                vec![
+                    funded(1, "1.5", "0.25", false),
+                    funded(2, "-0.0001", "0", true)
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn test_write_csv_columns() {
+        let eur = ClientSnapshot {
+            currency: Some("EUR".to_string()),
+            ..funded(1, "2", "0", false)
+        };
+        let limited = ClientSnapshot {
+            credit: Some("9".parse().unwrap()),
+            ..funded(2, "-1", "0", false)
+        };
+
+        assert_eq!(
+            "client,currency,available,held,total,locked,credit\n\
+             1,EUR,2.0000,0.0000,2.0000,false,0.0000\n\
+             2,,-1.0000,0.0000,-1.0000,false,9.0000\n",
+            write(OutputFormat::Csv, vec![eur, limited])
+        );
+    }
+
+    #[test]
+    fn test_write_json() {
+        assert_eq!(
+            "[{\"client\":1,\"available\":\"1.5000\",\"held\":\"0.2500\",\"total\":\"1.7500\",\
+             \"locked\":false},\
+             {\"client\":2,\"available\":\"0.0000\",\"held\":\"0.0000\",\"total\":\"0.0000\",\
+             \"locked\":true}]\n",
+            write(
+                OutputFormat::Json,
+                vec![funded(1, "1.5", "0.25", false), funded(2, "0", "0", true)]
+            )
+        );
+    }
+
+    #[test]
+    fn test_write_json_lines() {
+        assert_eq!(
+            "{\"client\":1,\"available\":\"1.5000\",\"held\":\"0.2500\",\"total\":\"1.7500\",\
+             \"locked\":false}\n\
+             {\"client\":2,\"available\":\"0.0000\",\"held\":\"0.0000\",\"total\":\"0.0000\",\
+             \"locked\":true}\n",
+            write(
+                OutputFormat::JsonLines,
+                vec![funded(1, "1.5", "0.25", false), funded(2, "0", "0", true)]
+            )
+        );
     }
 }
@@ -2,47 +2,292 @@ use crate::{
     common::ClientId,
     transaction::{TxId, TxType},
 };
+use serde::Serialize;
 
 #[derive(PartialEq)]
 pub enum Message {
     NotEnoughFunds(ClientId, TxId, TxType),
+    CreditLimitExceeded(ClientId, TxId, TxType),
     AlreadyInDispute(ClientId, TxId, TxType),
-    AlreadyDisputed(ClientId, TxId, TxType),
+    AlreadyResolved(ClientId, TxId, TxType),
+    AlreadyChargedBack(ClientId, TxId, TxType),
+    AlreadyClawedBack(ClientId, TxId, TxType),
+    DisputeLimitReached(ClientId, TxId, TxType),
+    NotEnoughFundsForDispute(ClientId, TxId, TxType),
+    WithdrawalDisputeNotAllowed(ClientId, TxId, TxType),
     NotInDispute(ClientId, TxId, TxType),
+    DisputeAmountExceeded(ClientId, TxId, TxType),
+    DisputedAmountNotFound(ClientId, TxId, TxType),
     AccountIsLocked(ClientId, TxId, TxType),
+    AccountIsClosed(ClientId, TxId, TxType),
+    AccountNotLocked(ClientId, TxId, TxType),
+    AdminNotAllowed(ClientId, TxId, TxType),
     TransactionExist(ClientId, TxId, TxType),
+    TransactionExistForOtherClient(ClientId, TxId, TxType),
+    TransactionOfOtherClient(ClientId, TxId, TxType),
+    InvalidDestination(ClientId, TxId, TxType),
+    CurrencyMismatch(ClientId, TxId, TxType),
+    RateNotFound(ClientId, TxId, TxType),
+    PartialExchangeDispute(ClientId, TxId, TxType),
+    SystemTransaction(ClientId, TxId, TxType),
+    ValueOverflow(ClientId, TxId, TxType),
     UnknownTransaction(ClientId, TxId),
+    UnknownClient(ClientId, TxId, TxType),
+    /// Dispute made available funds negative
+    NegativeAvailable(ClientId, TxId, TxType),
+    /// Withdrawal was reversed to cover a dispute
+    ClawedBack(ClientId, TxId, TxType),
 }
 
-impl std::fmt::Debug for Message {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut get_msg = |ty, tx, c, msg| {
-            f.write_fmt(format_args!(
-                "ERROR: Cannot process {:?} transaction {} for client {}. {}.",
-                ty, tx, c, msg
-            ))
-        };
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Transaction was processed but needs attention
+    Warning,
+    /// Transaction was rejected
+    Error,
+}
+
+/// Parts of the message: client, transaction, transaction type, stable code and reason.
+type MessageInfo = (ClientId, TxId, Option<TxType>, &'static str, &'static str);
+
+impl Message {
+    pub fn client(&self) -> ClientId {
+        self.info().0
+    }
+
+    pub fn tx(&self) -> TxId {
+        self.info().1
+    }
+
+    /// Type of the processed transaction, `None` if the transaction is unknown.
+    pub fn tx_type(&self) -> Option<TxType> {
+        self.info().2
+    }
+
+    /// Stable machine-readable code of the message. Codes never change once released.
+    pub fn code(&self) -> &'static str {
+        self.info().3
+    }
 
+    pub fn severity(&self) -> Severity {
         match self {
-            Self::NotEnoughFunds(c, tx, ty) => get_msg(ty, tx, c, "Not enough funds"),
-            Self::AlreadyInDispute(c, tx, ty) => {
-                get_msg(ty, tx, c, "Transaction already in dispute")
+            Self::NegativeAvailable(..) | Self::ClawedBack(..) => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+
+    /// Human readable text of the message.
+    pub fn text(&self) -> String {
+        let (c, tx, ty, _, reason) = self.info();
+
+        match ty {
+            Some(ty) if self.severity() == Severity::Warning => {
+                format!("{:?} transaction {} for client {}. {}.", ty, tx, c, reason)
+            }
+            Some(ty) => format!(
+                "Cannot process {:?} transaction {} for client {}. {}.",
+                ty, tx, c, reason
+            ),
+            None => format!(
+                "Cannot process transaction {} for client {}. {}.",
+                tx, c, reason
+            ),
+        }
+    }
+
+    fn info(&self) -> MessageInfo {
+        match *self {
+            Self::NotEnoughFunds(c, tx, ty) => {
+                (c, tx, Some(ty), "not_enough_funds", "Not enough funds")
+            }
+            Self::CreditLimitExceeded(c, tx, ty) => (
+                c,
+                tx,
+                Some(ty),
+                "credit_limit_exceeded",
+                "Not enough funds within the credit limit",
+            ),
+            Self::AlreadyInDispute(c, tx, ty) => (
+                c,
+                tx,
+                Some(ty),
+                "already_in_dispute",
+                "Transaction already in dispute",
+            ),
+            Self::AlreadyResolved(c, tx, ty) => (
+                c,
+                tx,
+                Some(ty),
+                "already_resolved",
+                "Dispute of the transaction was already resolved",
+            ),
+            Self::AlreadyChargedBack(c, tx, ty) => (
+                c,
+                tx,
+                Some(ty),
+                "already_charged_back",
+                "Transaction was charged back",
+            ),
+            Self::AlreadyClawedBack(c, tx, ty) => (
+                c,
+                tx,
+                Some(ty),
+                "already_clawed_back",
+                "Transaction was clawed back",
+            ),
+            Self::DisputeLimitReached(c, tx, ty) => (
+                c,
+                tx,
+                Some(ty),
+                "dispute_limit_reached",
+                "Transaction reached the maximum number of disputes",
+            ),
+            Self::NotEnoughFundsForDispute(c, tx, ty) => (
+                c,
+                tx,
+                Some(ty),
+                "not_enough_funds_for_dispute",
+                "Not enough available funds to hold disputed amount",
+            ),
+            Self::WithdrawalDisputeNotAllowed(c, tx, ty) => (
+                c,
+                tx,
+                Some(ty),
+                "withdrawal_dispute_not_allowed",
+                "Withdrawals cannot be disputed",
+            ),
+            Self::NotInDispute(c, tx, ty) => (
+                c,
+                tx,
+                Some(ty),
+                "not_in_dispute",
+                "Transaction is not in dispute",
+            ),
+            Self::DisputeAmountExceeded(c, tx, ty) => (
+                c,
+                tx,
+                Some(ty),
+                "dispute_amount_exceeded",
+                "Disputed amount exceeds the undisputed amount of the transaction",
+            ),
+            Self::DisputedAmountNotFound(c, tx, ty) => (
+                c,
+                tx,
+                Some(ty),
+                "disputed_amount_not_found",
+                "No open dispute of the transaction has this amount",
+            ),
+            Self::AccountIsLocked(c, tx, ty) => {
+                (c, tx, Some(ty), "account_locked", "Account is locked")
             }
-            Self::AlreadyDisputed(c, tx, ty) => {
-                get_msg(ty, tx, c, "Transaction was already disputed")
+            Self::AccountIsClosed(c, tx, ty) => {
+                (c, tx, Some(ty), "account_closed", "Account is closed")
             }
-            Self::NotInDispute(c, tx, ty) => get_msg(ty, tx, c, "Transaction is not in dispute"),
-            Self::AccountIsLocked(c, tx, ty) => get_msg(ty, tx, c, "Account is locked"),
-            Self::TransactionExist(c, tx, ty) => get_msg(
-                ty,
+            Self::AccountNotLocked(c, tx, ty) => (
+                c,
+                tx,
+                Some(ty),
+                "account_not_locked",
+                "Account is not locked",
+            ),
+            Self::AdminNotAllowed(c, tx, ty) => (
+                c,
                 tx,
+                Some(ty),
+                "admin_not_allowed",
+                "Administrative operations are not allowed",
+            ),
+            Self::TransactionExist(c, tx, ty) => (
                 c,
+                tx,
+                Some(ty),
+                "transaction_exists",
                 "Transaction with the same id was already processed",
             ),
-            Self::UnknownTransaction(c, tx) => f.write_fmt(format_args!(
-                "ERROR: Cannot process transaction {} for client {}. Transaction is unknown.",
-                tx, c
-            )),
+            Self::TransactionExistForOtherClient(c, tx, ty) => (
+                c,
+                tx,
+                Some(ty),
+                "transaction_exists_for_other_client",
+                "Transaction with the same id was already processed for another client",
+            ),
+            Self::TransactionOfOtherClient(c, tx, ty) => (
+                c,
+                tx,
+                Some(ty),
+                "transaction_of_other_client",
+                "Transaction belongs to another client",
+            ),
+            Self::InvalidDestination(c, tx, ty) => (
+                c,
+                tx,
+                Some(ty),
+                "invalid_destination",
+                "Transfer destination is missing or the same as the source",
+            ),
+            Self::CurrencyMismatch(c, tx, ty) => (
+                c,
+                tx,
+                Some(ty),
+                "currency_mismatch",
+                "Currency differs from the currency of the disputed transaction",
+            ),
+            Self::RateNotFound(c, tx, ty) => (
+                c,
+                tx,
+                Some(ty),
+                "rate_not_found",
+                "No exchange rate between the currencies on the date of the transaction",
+            ),
+            Self::PartialExchangeDispute(c, tx, ty) => (
+                c,
+                tx,
+                Some(ty),
+                "partial_exchange_dispute",
+                "Exchange can be disputed only as a whole",
+            ),
+            Self::SystemTransaction(c, tx, ty) => (
+                c,
+                tx,
+                Some(ty),
+                "system_transaction",
+                "Transaction type is reserved for the system",
+            ),
+            Self::ValueOverflow(c, tx, ty) => {
+                (c, tx, Some(ty), "value_overflow", "Balance overflow")
+            }
+            Self::UnknownTransaction(c, tx) => {
+                (c, tx, None, "unknown_transaction", "Transaction is unknown")
+            }
+            Self::UnknownClient(c, tx, ty) => {
+                (c, tx, Some(ty), "unknown_client", "Client is unknown")
+            }
+            Self::NegativeAvailable(c, tx, ty) => (
+                c,
+                tx,
+                Some(ty),
+                "negative_available",
+                "Dispute made available funds negative",
+            ),
+            Self::ClawedBack(c, tx, ty) => (
+                c,
+                tx,
+                Some(ty),
+                "clawed_back",
+                "Clawed back to cover dispute of an earlier transaction",
+            ),
         }
     }
 }
+
+impl std::fmt::Debug for Message {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity() {
+            Severity::Warning => "WARNING",
+            Severity::Error => "ERROR",
+        };
+
+        f.write_fmt(format_args!("{}: {}", severity, self.text()))
+    }
+}
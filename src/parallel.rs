@@ -0,0 +1,277 @@
+use crate::{
+    client::{Client, ClientSnapshot},
+    common::ClientId,
+    message::Message,
+    process::{shard, Engine},
+    transaction::{Tx, TxType},
+};
+use std::{
+    sync::mpsc::{self, Sender, SyncSender},
+    thread::{self, JoinHandle},
+};
+
+/// Maximum number of transactions waiting in the queue of a single worker.
+const QUEUE_SIZE: usize = 1024;
+
+/// Messages tagged with the sequence number of the transaction that produced them.
+type SeqMessages = Vec<(u64, Message)>;
+
+enum Job {
+    /// Transaction with its sequence number
+    Process(u64, Tx),
+    /// Client whose transaction was rejected by the registry
+    Touch(ClientId),
+    /// Send the client to the calling thread to process a transfer
+    Lend(ClientId, Sender<Option<Client>>),
+    /// Client returned after the transfer
+    Return(Box<Client>),
+}
+
+struct Worker {
+    sender: SyncSender<Job>,
+    handle: JoinHandle<(Engine, SeqMessages)>,
+}
+
+/// Processes transactions on several threads.
+///
+/// Each worker owns a shard of clients selected by client id so transactions of one client
+/// are processed in their input order. Transaction ids registry stays on the calling thread.
+/// Transfers and their disputes change two clients, so both clients are lent to the calling
+/// thread once their workers reach the transfer and are returned after it is processed.
+/// Results are merged deterministically: clients are sorted by id and messages follow the
+/// input order of transactions.
+pub struct ParallelEngine {
+    /// Engine without clients that keeps registry and settings
+    engine: Engine,
+    workers: Vec<Worker>,
+    seq: u64,
+    messages: SeqMessages,
+}
+
+impl ParallelEngine {
+    pub fn new(threads: usize) -> Self {
+        Self::with_engine(Engine::new(), threads)
+    }
+
+    /// Continues processing from the state of the `engine`.
+    pub fn with_engine(engine: Engine, threads: usize) -> Self {
+        let (engine, engines) = engine.split(threads.max(1));
+        let workers = engines
+            .into_iter()
+            .map(|mut engine| {
+                let (sender, receiver) = mpsc::sync_channel(QUEUE_SIZE);
+                let handle = thread::spawn(move || {
+                    let mut messages = vec![];
+                    let mut seq_messages = vec![];
+
+                    for job in receiver {
+                        match job {
+                            Job::Process(seq, tx) => {
+                                engine.apply(&tx, &mut messages);
+                                seq_messages.extend(messages.drain(..).map(|m| (seq, m)));
+                            }
+                            Job::Touch(id) => engine.touch(id),
+                            Job::Lend(id, reply) => reply
+                                .send(engine.take(id))
+                                .expect("Calling thread stopped unexpectedly"),
+                            Job::Return(client) => engine.put(*client),
+                        }
+                    }
+
+                    (engine, seq_messages)
+                });
+
+                Worker { sender, handle }
+            })
+            .collect();
+
+        Self {
+            seq: engine.seq,
+            engine,
+            workers,
+            messages: vec![],
+        }
+    }
+
+    pub fn process(&mut self, tx: &Tx) {
+        let seq = self.seq;
+        let mut messages = vec![];
+
+        self.seq += 1;
+
+        if !self.engine.registry.validate(tx, &mut messages) {
+            self.messages.extend(messages.into_iter().map(|m| (seq, m)));
+
+            // Client appears in the output even if its transaction is rejected by the registry
+            self.send(tx.client_id, Job::Touch(tx.client_id));
+
+            if let (TxType::Transfer, Some(destination)) = (tx.ty, tx.destination()) {
+                self.send(destination, Job::Touch(destination));
+            }
+
+            return;
+        }
+
+        match self.engine.registry.counterparty(tx) {
+            Some(destination) => self.process_transfer(seq, tx, destination),
+            None => self.send(tx.client_id, Job::Process(seq, tx.clone())),
+        }
+    }
+
+    /// Processes transfer on the calling thread with clients lent by their workers.
+    fn process_transfer(&mut self, seq: u64, tx: &Tx, destination: ClientId) {
+        let mut engine = Engine::with_config(self.engine.config().clone());
+        let mut messages = vec![];
+
+        for id in [tx.client_id, destination] {
+            let (reply, receiver) = mpsc::channel();
+
+            self.send(id, Job::Lend(id, reply));
+
+            if let Some(client) = receiver.recv().expect("Worker thread stopped unexpectedly") {
+                engine.put(client);
+            }
+        }
+
+        if engine.apply_transfer(tx, destination, &mut messages) {
+            self.engine.registry.accept(tx);
+        }
+
+        self.messages.extend(messages.into_iter().map(|m| (seq, m)));
+
+        for id in [tx.client_id, destination] {
+            if let Some(client) = engine.take(id) {
+                self.send(id, Job::Return(Box::new(client)));
+            }
+        }
+    }
+
+    fn send(&self, id: ClientId, job: Job) {
+        self.workers[shard(id, self.workers.len())]
+            .sender
+            .send(job)
+            .expect("Worker thread stopped unexpectedly");
+    }
+
+    /// Waits for all workers and returns clients sorted by id and messages in input order.
+    pub fn finish(self) -> (Vec<ClientSnapshot>, Vec<Message>) {
+        let (engine, messages) = self.into_engine();
+
+        (engine.clients(), messages)
+    }
+
+    /// Waits for all workers and returns the engine with their combined state and messages
+    /// in input order.
+    pub fn into_engine(self) -> (Engine, Vec<Message>) {
+        let mut engines = vec![];
+        let mut messages = self.messages;
+
+        for worker in self.workers {
+            drop(worker.sender);
+
+            let (engine, worker_messages) = worker.handle.join().expect("Worker thread panicked");
+
+            engines.push(engine);
+            messages.extend(worker_messages);
+        }
+
+        messages.sort_by_key(|(seq, _)| *seq);
+
+        let mut engine = self.engine.join(engines);
+
+        engine.seq = self.seq;
+
+        (engine, messages.into_iter().map(|(_, m)| m).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::Config,
+        profile::Profile,
+        transaction::{TxDetails, TxType},
+        value::Value,
+    };
+
+    /// Mix of valid and failing transactions for several clients with shared tx ids.
+    fn transactions() -> Vec<Tx> {
+        let amount = Value::from_units(10_000);
+
+        (0..2000u32)
+            .flat_map(|i| {
+                let client = (i % 7) as u16;
+                let id = i / 2;
+                let transfer =
+                    Tx::new(TxType::Transfer, client, id + 10000, amount).with_details(TxDetails {
+                        destination: Some((client + 3) % 7),
+                        ..TxDetails::default()
+                    });
+
+                vec![
+                    Tx::new(TxType::Deposit, client, id, amount),
+                    Tx::new(TxType::Withdrawal, client, id + 5000, amount + amount),
+                    transfer,
+                    Tx::new(TxType::Dispute, client, id + 10000, Value::ZERO),
+                    Tx::new(TxType::Dispute, client, id, Value::ZERO),
+                    Tx::new(TxType::Chargeback, (client + 1) % 7, id, Value::ZERO),
+                    Tx::new(TxType::Resolve, client, id + 10000, Value::ZERO),
+                ]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_same_as_single_thread() {
+        test_config(Config::default());
+    }
+
+    #[test]
+    fn test_strict_same_as_single_thread() {
+        test_config(Config {
+            strict: true,
+            ..Config::default()
+        });
+    }
+
+    /// Engine with registered odd clients.
+    fn initial(config: &Config) -> Engine {
+        let mut engine = Engine::with_config(config.clone());
+
+        for client in (1..7).step_by(2) {
+            engine.set_profile(&Profile::new(client)).unwrap();
+        }
+
+        engine
+    }
+
+    fn test_config(config: Config) {
+        let transactions = transactions();
+        let mut engine = initial(&config);
+        let mut expected_messages = vec![];
+
+        for tx in &transactions {
+            engine.process(tx, &mut expected_messages);
+        }
+
+        let expected_clients = engine.clients();
+
+        for threads in 1..=4 {
+            let mut engine = ParallelEngine::with_engine(initial(&config), threads);
+
+            for tx in &transactions {
+                engine.process(tx);
+            }
+
+            let (clients, messages) = engine.finish();
+
+            assert_eq!(expected_clients, clients, "clients, threads: {}", threads);
+            assert_eq!(
+                expected_messages, messages,
+                "messages, threads: {}",
+                threads
+            );
+        }
+    }
+}
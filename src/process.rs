@@ -1,23 +1,261 @@
-use crate::{client::Client, message::Message, transaction::Tx};
+use crate::{
+    client::{Client, ClientSnapshot},
+    common::ClientId,
+    config::Config,
+    message::Message,
+    registry::Registry,
+    transaction::{Tx, TxId, TxState, TxType},
+};
 use std::collections::HashMap;
 
-pub fn process(transactions: &[Tx], messages: &mut Vec<Message>) -> Vec<Client> {
-    let mut clients = HashMap::new();
+/// Order of clients in the engine output.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SortOrder {
+    /// Ascending client id
+    #[default]
+    Client,
+    /// Unspecified order that may differ from run to run
+    None,
+}
 
-    for tx in transactions {
-        let client = clients
+/// Processes transactions one at a time, keeping only client state in memory.
+///
+/// ```
+/// use transactions_test::{Engine, Tx, TxType};
+///
+/// let mut engine = Engine::new();
+/// let mut messages = vec![];
+///
+/// engine.process(
+///     &Tx::new(TxType::Deposit, 1, 1, "1.5".parse().unwrap()),
+///     &mut messages,
+/// );
+///
+/// assert!(messages.is_empty());
+/// assert_eq!("1.5000", engine.client(1).unwrap().available.to_string());
+/// ```
+#[derive(Default)]
+pub struct Engine {
+    pub(crate) clients: HashMap<ClientId, Client>,
+    pub(crate) registry: Registry,
+    pub(crate) config: Config,
+    /// Number of transactions processed since the first run, including rejected ones
+    pub(crate) seq: u64,
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_config(config: Config) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Number of transactions processed since the first run, including the ones restored
+    /// from the state and rejected ones.
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    /// Changes settings for the following transactions, e.g. after the state is loaded.
+    pub fn set_config(&mut self, config: Config) {
+        self.config = config;
+    }
+
+    /// Returns `true` if transaction was accepted, otherwise the reason is reported in
+    /// `messages`.
+    pub fn process(&mut self, tx: &Tx, messages: &mut Vec<Message>) -> bool {
+        self.seq += 1;
+
+        // Client appears in the output even if its transaction is rejected
+        self.touch(tx.client_id);
+
+        if let (TxType::Transfer, Some(destination)) = (tx.ty, tx.destination()) {
+            self.touch(destination);
+        }
+
+        if !self.registry.validate(tx, messages) {
+            return false;
+        }
+
+        match self.registry.counterparty(tx) {
+            Some(destination) => {
+                let accepted = self.apply_transfer(tx, destination, messages);
+
+                if accepted {
+                    self.registry.accept(tx);
+                }
+
+                accepted
+            }
+            None => self.apply(tx, messages),
+        }
+    }
+
+    /// Processes single client transaction that was already validated against the registry.
+    pub(crate) fn apply(&mut self, tx: &Tx, messages: &mut Vec<Message>) -> bool {
+        if !self.check_known(tx.client_id, tx, messages) {
+            return false;
+        }
+
+        let client = self
+            .clients
             .entry(tx.client_id)
             .or_insert_with(|| Client::new(tx.client_id));
 
-        client.process(tx, messages);
+        client.process(tx, &self.config, messages)
+    }
+
+    /// Processes transfer or dispute of the transfer to the `destination` client that was
+    /// already validated against the registry.
+    pub(crate) fn apply_transfer(
+        &mut self,
+        tx: &Tx,
+        destination: ClientId,
+        messages: &mut Vec<Message>,
+    ) -> bool {
+        if !self.check_known(tx.client_id, tx, messages)
+            || !self.check_known(destination, tx, messages)
+        {
+            return false;
+        }
+
+        let mut source = self
+            .take(tx.client_id)
+            .unwrap_or_else(|| Client::new(tx.client_id));
+        let destination = self
+            .clients
+            .entry(destination)
+            .or_insert_with(|| Client::new(destination));
+        let accepted = source.process_transfer(destination, tx, &self.config, messages);
+
+        self.put(source);
+
+        accepted
+    }
+
+    /// Checks that the client is known if the engine is strict.
+    fn check_known(&self, id: ClientId, tx: &Tx, messages: &mut Vec<Message>) -> bool {
+        if self.config.strict && !self.clients.contains_key(&id) {
+            messages.push(Message::UnknownClient(id, tx.tx_id, tx.ty));
+            return false;
+        }
+
+        true
+    }
+
+    /// Removes client from the engine.
+    pub(crate) fn take(&mut self, id: ClientId) -> Option<Client> {
+        self.clients.remove(&id)
+    }
+
+    /// Adds client removed from another engine.
+    pub(crate) fn put(&mut self, client: Client) {
+        self.clients.insert(client.id, client);
+    }
+
+    /// Registers client without transactions, unknown clients are not registered by the strict
+    /// engine.
+    pub(crate) fn touch(&mut self, id: ClientId) {
+        if !self.config.strict {
+            self.client_mut(id);
+        }
+    }
+
+    pub(crate) fn client_mut(&mut self, id: ClientId) -> &mut Client {
+        self.clients.entry(id).or_insert_with(|| Client::new(id))
+    }
+
+    /// Splits engine into the engine without clients that keeps registry and settings, and
+    /// engines owning shards of clients.
+    pub(crate) fn split(mut self, shards: usize) -> (Engine, Vec<Engine>) {
+        let mut engines: Vec<_> = (0..shards)
+            .map(|_| Engine::with_config(self.config.clone()))
+            .collect();
+
+        for (id, client) in self.clients.drain() {
+            engines[shard(id, shards)].clients.insert(id, client);
+        }
+
+        (self, engines)
+    }
+
+    /// Joins engines owning shards of clients back into this engine.
+    pub(crate) fn join(mut self, engines: Vec<Engine>) -> Self {
+        self.clients
+            .extend(engines.into_iter().flat_map(|e| e.clients));
+        self
+    }
+
+    /// Current state of the client in the account currency, `None` if client has no
+    /// transactions.
+    pub fn client(&self, id: ClientId) -> Option<ClientSnapshot> {
+        self.clients.get(&id).map(Client::snapshot)
+    }
+
+    /// Current state of all clients sorted by client id, one per client and currency.
+    pub fn clients(&self) -> Vec<ClientSnapshot> {
+        self.sorted_clients(SortOrder::default())
+    }
+
+    /// Current state of all clients in the requested order.
+    pub fn sorted_clients(&self, order: SortOrder) -> Vec<ClientSnapshot> {
+        let mut clients: Vec<_> = self.clients.values().flat_map(Client::snapshots).collect();
+
+        match order {
+            SortOrder::Client => clients.sort_by_key(|c| c.id),
+            SortOrder::None => (),
+        }
+
+        clients
     }
 
-    clients.drain().map(|(_, v)| v).collect()
+    /// Accepted deposit or withdrawal transaction with its dispute history.
+    pub fn transaction(&self, tx_id: TxId) -> Option<&Tx> {
+        let owner = self.registry.owner(tx_id)?;
+
+        self.clients.get(&owner)?.transactions.get(&tx_id)
+    }
+
+    /// Current outcome of the accepted deposit or withdrawal transaction.
+    pub fn tx_state(&self, tx_id: TxId) -> Option<TxState> {
+        self.transaction(tx_id).map(|tx| tx.state)
+    }
+}
+
+/// Index of the shard that owns the client.
+pub(crate) fn shard(id: ClientId, shards: usize) -> usize {
+    id as usize % shards
+}
+
+/// Processes a batch of transactions with a fresh engine. Clients are sorted by id.
+pub fn process(transactions: &[Tx], messages: &mut Vec<Message>) -> Vec<ClientSnapshot> {
+    let mut engine = Engine::new();
+
+    for tx in transactions {
+        engine.process(tx, messages);
+    }
+
+    engine.clients()
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{message::Message, transaction::*};
+    use super::*;
+    use crate::{
+        client::AccountStatus,
+        config::{Config, DisputePolicy, LockPolicy, WithdrawalDisputePolicy},
+        profile::Profile,
+        transaction::*,
+    };
     use helper::*;
 
     #[test]
@@ -28,8 +266,8 @@ mod tests {
     #[test]
     fn test_single_transaction() {
         test_process(
-            &[tx_deposit(1, 1, 5.0)],
-            &[client(1, 5.0, 0.0, 5.0, false)],
+            &[tx_deposit(1, 1, "5.0")],
+            &[client(1, "5.0", "0.0", "5.0", false)],
             &[],
         );
     }
@@ -37,8 +275,8 @@ mod tests {
     #[test]
     fn test_transaction_same_id_fail() {
         test_process(
-            &[tx_deposit(1, 1, 5.0), tx_deposit(1, 1, 2.0)],
-            &[client(1, 5.0, 0.0, 5.0, false)],
+            &[tx_deposit(1, 1, "5.0"), tx_deposit(1, 1, "2.0")],
+            &[client(1, "5.0", "0.0", "5.0", false)],
             &[Message::TransactionExist(1, 1, TxType::Deposit)],
         );
     }
@@ -46,8 +284,8 @@ mod tests {
     #[test]
     fn test_withdrawal_empty_fail() {
         test_process(
-            &[tx_withdrawal(1, 1, 5.0)],
-            &[client(1, 0.0, 0.0, 0.0, false)],
+            &[tx_withdrawal(1, 1, "5.0")],
+            &[client(1, "0.0", "0.0", "0.0", false)],
             &[Message::NotEnoughFunds(1, 1, TxType::Withdrawal)],
         );
     }
@@ -55,17 +293,25 @@ mod tests {
     #[test]
     fn test_withdrawal_full() {
         test_process(
-            &[tx_deposit(1, 1, 5.0), tx_withdrawal(1, 2, 5.0)],
-            &[client(1, 0.0, 0.0, 0.0, false)],
+            &[tx_deposit(1, 1, "5.0"), tx_withdrawal(1, 2, "5.0")],
+            &[client(1, "0.0", "0.0", "0.0", false)],
             &[],
         );
     }
 
+    #[test]
+    fn test_tx_size() {
+        // Retained deposits and withdrawals keep the rarely used fields out of line
+        let size = std::mem::size_of::<Tx>();
+
+        assert!(size <= 48, "{}", size);
+    }
+
     #[test]
     fn test_withdrawal_partial() {
         test_process(
-            &[tx_deposit(1, 1, 5.0), tx_withdrawal(1, 2, 2.0)],
-            &[client(1, 3.0, 0.0, 3.0, false)],
+            &[tx_deposit(1, 1, "5.0"), tx_withdrawal(1, 2, "2.0")],
+            &[client(1, "3.0", "0.0", "3.0", false)],
             &[],
         );
     }
@@ -73,33 +319,151 @@ mod tests {
     #[test]
     fn test_withdrawal_exeeding() {
         test_process(
-            &[tx_deposit(1, 1, 5.0), tx_withdrawal(1, 2, 7.0)],
-            &[client(1, 5.0, 0.0, 5.0, false)],
+            &[tx_deposit(1, 1, "5.0"), tx_withdrawal(1, 2, "7.0")],
+            &[client(1, "5.0", "0.0", "5.0", false)],
             &[Message::NotEnoughFunds(1, 2, TxType::Withdrawal)],
         );
     }
 
     // Dispute transaction before the last one
     #[test]
-    #[ignore = "Not clear expected behavior"]
     fn test_dispute_prev() {
-        test_process(
+        test_process_config(
+            &config(DisputePolicy::ClawBack),
             &[
-                tx_deposit(1, 1, 5.0),
-                tx_withdrawal(1, 2, 5.0),
+                tx_deposit(1, 1, "5.0"),
+                tx_withdrawal(1, 2, "5.0"),
                 tx_dispute(1, 1),
                 tx_chargeback(1, 1),
             ],
-            &[client(1, 0.0, 5.0, 5.0, false)],
-            &[],
+            &[client(1, "0.0", "0.0", "0.0", true)],
+            &[Message::ClawedBack(1, 2, TxType::Withdrawal)],
+        );
+    }
+
+    #[test]
+    fn test_dispute_prev_reject() {
+        test_process_config(
+            &config(DisputePolicy::Reject),
+            &[
+                tx_deposit(1, 1, "5.0"),
+                tx_withdrawal(1, 2, "5.0"),
+                tx_dispute(1, 1),
+                tx_chargeback(1, 1),
+            ],
+            &[client(1, "0.0", "0.0", "0.0", false)],
+            &[
+                Message::NotEnoughFundsForDispute(1, 1, TxType::Deposit),
+                Message::NotInDispute(1, 1, TxType::Deposit),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_dispute_prev_negative() {
+        test_process_config(
+            &config(DisputePolicy::Negative),
+            &[
+                tx_deposit(1, 1, "5.0"),
+                tx_withdrawal(1, 2, "5.0"),
+                tx_dispute(1, 1),
+            ],
+            &[client(1, "-5.0", "5.0", "0.0", false)],
+            &[Message::NegativeAvailable(1, 1, TxType::Deposit)],
+        );
+    }
+
+    #[test]
+    fn test_dispute_prev_negative_chargeback() {
+        test_process_config(
+            &config(DisputePolicy::Negative),
+            &[
+                tx_deposit(1, 1, "5.0"),
+                tx_withdrawal(1, 2, "5.0"),
+                tx_dispute(1, 1),
+                tx_chargeback(1, 1),
+            ],
+            &[client(1, "-5.0", "0.0", "-5.0", true)],
+            &[Message::NegativeAvailable(1, 1, TxType::Deposit)],
+        );
+    }
+
+    #[test]
+    fn test_dispute_prev_claw_back_partial() {
+        test_process_config(
+            &config(DisputePolicy::ClawBack),
+            &[
+                tx_deposit(1, 1, "5.0"),
+                tx_deposit(1, 2, "3.0"),
+                tx_withdrawal(1, 3, "2.0"),
+                tx_withdrawal(1, 4, "4.0"),
+                tx_dispute(1, 1),
+                tx_resolve(1, 1),
+            ],
+            &[client(1, "2.0", "0.0", "2.0", false)],
+            &[Message::ClawedBack(1, 4, TxType::Withdrawal)],
+        );
+    }
+
+    #[test]
+    fn test_dispute_prev_claw_back_resolve() {
+        test_process_config(
+            &config(DisputePolicy::ClawBack),
+            &[
+                tx_deposit(1, 1, "5.0"),
+                tx_withdrawal(1, 2, "5.0"),
+                tx_dispute(1, 1),
+                tx_resolve(1, 1),
+            ],
+            &[client(1, "0.0", "0.0", "0.0", false)],
+            &[Message::ClawedBack(1, 2, TxType::Withdrawal)],
+        );
+    }
+
+    #[test]
+    fn test_transfer_dispute_claw_back_resolve() {
+        test_process_config(
+            &config(DisputePolicy::ClawBack),
+            &[
+                tx_deposit(1, 1, "5.0"),
+                tx_transfer(1, 2, 2, "4.0"),
+                tx_withdrawal(2, 3, "3.0"),
+                tx_dispute(1, 2),
+                tx_resolve(1, 2),
+            ],
+            &[
+                client(1, "1.0", "0.0", "1.0", false),
+                client(2, "1.0", "0.0", "1.0", false),
+            ],
+            &[Message::ClawedBack(2, 3, TxType::Withdrawal)],
+        );
+    }
+
+    #[test]
+    fn test_dispute_prev_claw_back_earlier_withdrawal() {
+        test_process_config(
+            &config(DisputePolicy::ClawBack),
+            &[
+                tx_deposit(1, 1, "5.0"),
+                tx_withdrawal(1, 2, "4.0"),
+                tx_deposit(1, 3, "2.0"),
+                tx_withdrawal(1, 4, "2.0"),
+                tx_dispute(1, 3),
+                tx_dispute(1, 4),
+            ],
+            &[client(1, "1.0", "2.0", "3.0", false)],
+            &[
+                Message::ClawedBack(1, 4, TxType::Withdrawal),
+                Message::AlreadyClawedBack(1, 4, TxType::Withdrawal),
+            ],
         );
     }
 
     #[test]
     fn test_deposit_dispute() {
         test_process(
-            &[tx_deposit(1, 1, 5.0), tx_dispute(1, 1)],
-            &[client(1, 0.0, 5.0, 5.0, false)],
+            &[tx_deposit(1, 1, "5.0"), tx_dispute(1, 1)],
+            &[client(1, "0.0", "5.0", "5.0", false)],
             &[],
         );
     }
@@ -107,8 +471,8 @@ mod tests {
     #[test]
     fn test_deposit_dispute_unknown_fail() {
         test_process(
-            &[tx_deposit(1, 1, 5.0), tx_dispute(1, 2)],
-            &[client(1, 5.0, 0.0, 5.0, false)],
+            &[tx_deposit(1, 1, "5.0"), tx_dispute(1, 2)],
+            &[client(1, "5.0", "0.0", "5.0", false)],
             &[Message::UnknownTransaction(1, 2)],
         );
     }
@@ -116,8 +480,8 @@ mod tests {
     #[test]
     fn test_deposit_already_in_dispute_fail() {
         test_process(
-            &[tx_deposit(1, 1, 5.0), tx_dispute(1, 1), tx_dispute(1, 1)],
-            &[client(1, 0.0, 5.0, 5.0, false)],
+            &[tx_deposit(1, 1, "5.0"), tx_dispute(1, 1), tx_dispute(1, 1)],
+            &[client(1, "0.0", "5.0", "5.0", false)],
             &[Message::AlreadyInDispute(1, 1, TxType::Deposit)],
         );
     }
@@ -126,12 +490,12 @@ mod tests {
     fn test_withdrawal_already_in_dispute_fail() {
         test_process(
             &[
-                tx_deposit(1, 1, 5.0),
-                tx_withdrawal(1, 2, 3.0),
+                tx_deposit(1, 1, "5.0"),
+                tx_withdrawal(1, 2, "3.0"),
                 tx_dispute(1, 2),
                 tx_dispute(1, 2),
             ],
-            &[client(1, 5.0, -3.0, 2.0, false)],
+            &[client(1, "5.0", "-3.0", "2.0", false)],
             &[Message::AlreadyInDispute(1, 2, TxType::Withdrawal)],
         );
     }
@@ -139,8 +503,8 @@ mod tests {
     #[test]
     fn test_deposit_resolve() {
         test_process(
-            &[tx_deposit(1, 1, 5.0), tx_dispute(1, 1), tx_resolve(1, 1)],
-            &[client(1, 5.0, 0.0, 5.0, false)],
+            &[tx_deposit(1, 1, "5.0"), tx_dispute(1, 1), tx_resolve(1, 1)],
+            &[client(1, "5.0", "0.0", "5.0", false)],
             &[],
         );
     }
@@ -148,8 +512,8 @@ mod tests {
     #[test]
     fn test_deposit_resolve_unknown_fail() {
         test_process(
-            &[tx_deposit(1, 1, 5.0), tx_resolve(1, 2)],
-            &[client(1, 5.0, 0.0, 5.0, false)],
+            &[tx_deposit(1, 1, "5.0"), tx_resolve(1, 2)],
+            &[client(1, "5.0", "0.0", "5.0", false)],
             &[Message::UnknownTransaction(1, 2)],
         )
     }
@@ -157,8 +521,8 @@ mod tests {
     #[test]
     fn test_deposit_resolve_fail() {
         test_process(
-            &[tx_deposit(1, 1, 5.0), tx_resolve(1, 1)],
-            &[client(1, 5.0, 0.0, 5.0, false)],
+            &[tx_deposit(1, 1, "5.0"), tx_resolve(1, 1)],
+            &[client(1, "5.0", "0.0", "5.0", false)],
             &[Message::NotInDispute(1, 1, TxType::Deposit)],
         );
     }
@@ -167,232 +531,1730 @@ mod tests {
     fn test_deposit_resolve_dispute_fail() {
         test_process(
             &[
-                tx_deposit(1, 1, 5.0),
+                tx_deposit(1, 1, "5.0"),
                 tx_dispute(1, 1),
                 tx_resolve(1, 1),
                 tx_dispute(1, 1),
             ],
-            &[client(1, 5.0, 0.0, 5.0, false)],
-            &[Message::AlreadyDisputed(1, 1, TxType::Deposit)],
+            &[client(1, "5.0", "0.0", "5.0", false)],
+            &[Message::DisputeLimitReached(1, 1, TxType::Deposit)],
         );
     }
 
     #[test]
-    fn test_deposit_chargeback() {
-        test_process(
-            &[tx_deposit(1, 1, 5.0), tx_dispute(1, 1), tx_chargeback(1, 1)],
-            &[client(1, 0.0, 0.0, 0.0, true)],
+    fn test_deposit_resolve_dispute_again() {
+        test_process_config(
+            &max_disputes_config(2),
+            &[
+                tx_deposit(1, 1, "5.0"),
+                tx_dispute(1, 1),
+                tx_resolve(1, 1),
+                tx_dispute(1, 1),
+            ],
+            &[client(1, "0.0", "5.0", "5.0", false)],
             &[],
         );
     }
 
     #[test]
-    fn test_deposit_chargeback_unknown_fail() {
+    fn test_deposit_resolve_dispute_chargeback() {
+        test_process_config(
+            &max_disputes_config(2),
+            &[
+                tx_deposit(1, 1, "5.0"),
+                tx_dispute(1, 1),
+                tx_resolve(1, 1),
+                tx_dispute(1, 1),
+                tx_chargeback(1, 1),
+            ],
+            &[client(1, "0.0", "0.0", "0.0", true)],
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_deposit_dispute_limit_fail() {
+        test_process_config(
+            &max_disputes_config(2),
+            &[
+                tx_deposit(1, 1, "5.0"),
+                tx_dispute(1, 1),
+                tx_resolve(1, 1),
+                tx_dispute(1, 1),
+                tx_resolve(1, 1),
+                tx_dispute(1, 1),
+            ],
+            &[client(1, "5.0", "0.0", "5.0", false)],
+            &[Message::DisputeLimitReached(1, 1, TxType::Deposit)],
+        );
+    }
+
+    #[test]
+    fn test_deposit_chargeback_resolve_fail() {
+        test_process_config(
+            &max_disputes_config(2),
+            &[
+                tx_deposit(1, 1, "5.0"),
+                tx_dispute(1, 1),
+                tx_chargeback(1, 1),
+                tx_resolve(1, 1),
+            ],
+            &[client(1, "0.0", "0.0", "0.0", true)],
+            &[Message::AccountIsLocked(1, 1, TxType::Resolve)],
+        );
+    }
+
+    #[test]
+    fn test_dispute_history() {
+        let mut engine = Engine::with_config(max_disputes_config(3));
+        let mut messages = vec![];
+
+        for tx in &[
+            tx_deposit(1, 1, "5.0"),
+            tx_dispute(1, 1),
+            tx_resolve(1, 1),
+            tx_dispute(1, 1),
+            tx_resolve(1, 1),
+            tx_dispute(1, 1),
+        ] {
+            engine.process(tx, &mut messages);
+        }
+
+        let tx = engine.transaction(1).unwrap();
+
+        assert_eq!(TxState::InDispute, tx.state);
+        assert_eq!(
+            vec![
+                DisputeCycle {
+                    amount: value("5.0"),
+                    outcome: Some(TxType::Resolve),
+                    clawed_back: vec![]
+                },
+                DisputeCycle {
+                    amount: value("5.0"),
+                    outcome: Some(TxType::Resolve),
+                    clawed_back: vec![]
+                },
+                DisputeCycle {
+                    amount: value("5.0"),
+                    outcome: None,
+                    clawed_back: vec![]
+                },
+            ],
+            tx.disputes
+        );
+    }
+
+    #[test]
+    fn test_deposit_resolve_twice_fail() {
         test_process(
-            &[tx_deposit(1, 1, 5.0), tx_chargeback(1, 2)],
-            &[client(1, 5.0, 0.0, 5.0, false)],
-            &[Message::UnknownTransaction(1, 2)],
+            &[
+                tx_deposit(1, 1, "5.0"),
+                tx_dispute(1, 1),
+                tx_resolve(1, 1),
+                tx_resolve(1, 1),
+            ],
+            &[client(1, "5.0", "0.0", "5.0", false)],
+            &[Message::AlreadyResolved(1, 1, TxType::Deposit)],
         );
     }
 
     #[test]
-    fn test_account_locked() {
+    fn test_deposit_resolve_chargeback_fail() {
         test_process(
             &[
-                tx_deposit(1, 1, 5.0),
+                tx_deposit(1, 1, "5.0"),
                 tx_dispute(1, 1),
+                tx_resolve(1, 1),
                 tx_chargeback(1, 1),
-                tx_deposit(1, 2, 5.0),
             ],
-            &[client(1, 0.0, 0.0, 0.0, true)],
-            &[Message::AccountIsLocked(1, 2, TxType::Deposit)],
+            &[client(1, "5.0", "0.0", "5.0", false)],
+            &[Message::AlreadyResolved(1, 1, TxType::Deposit)],
         );
     }
 
     #[test]
-    fn test_withdrawal_dispute() {
+    fn test_tx_state() {
+        let mut engine = Engine::new();
+        let mut messages = vec![];
+
+        for tx in &[
+            tx_deposit(1, 1, "5.0"),
+            tx_deposit(1, 2, "5.0"),
+            tx_deposit(2, 3, "5.0"),
+            tx_deposit(2, 4, "5.0"),
+            tx_withdrawal(2, 5, "20.0"),
+            tx_dispute(1, 2),
+            tx_resolve(1, 2),
+            tx_dispute(2, 3),
+            tx_chargeback(2, 3),
+            tx_dispute(1, 1),
+        ] {
+            engine.process(tx, &mut messages);
+        }
+
+        assert_eq!(Some(TxState::InDispute), engine.tx_state(1));
+        assert_eq!(Some(TxState::Resolved), engine.tx_state(2));
+        assert_eq!(Some(TxState::ChargedBack), engine.tx_state(3));
+        assert_eq!(Some(TxState::Active), engine.tx_state(4));
+        assert_eq!(None, engine.tx_state(5));
+        assert_eq!(None, engine.tx_state(6));
+    }
+
+    #[test]
+    fn test_deposit_dispute_part() {
+        test_process(
+            &[tx_deposit(1, 1, "10.0"), tx_dispute_part(1, 1, "4.0")],
+            &[client(1, "6.0", "4.0", "10.0", false)],
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_deposit_dispute_parts() {
         test_process(
             &[
-                tx_deposit(1, 1, 5.0),
-                tx_withdrawal(1, 2, 5.0),
-                tx_dispute(1, 2),
+                tx_deposit(1, 1, "10.0"),
+                tx_dispute_part(1, 1, "4.0"),
+                tx_dispute_part(1, 1, "6.0"),
             ],
-            &[client(1, 5.0, -5.0, 0.0, false)],
+            &[client(1, "0.0", "10.0", "10.0", false)],
             &[],
         );
     }
 
     #[test]
-    fn test_withdrawal_dispute_unknown_fail() {
+    fn test_deposit_dispute_part_rest() {
         test_process(
             &[
-                tx_deposit(1, 1, 5.0),
-                tx_withdrawal(1, 2, 5.0),
-                tx_dispute(1, 3),
+                tx_deposit(1, 1, "10.0"),
+                tx_dispute_part(1, 1, "4.0"),
+                tx_dispute(1, 1),
+                tx_dispute(1, 1),
             ],
-            &[client(1, 0.0, 0.0, 0.0, false)],
-            &[Message::UnknownTransaction(1, 3)],
+            &[client(1, "0.0", "10.0", "10.0", false)],
+            &[Message::AlreadyInDispute(1, 1, TxType::Deposit)],
         );
     }
 
     #[test]
-    fn test_withdrawal_resolve() {
+    fn test_deposit_dispute_part_exceeded_fail() {
         test_process(
             &[
-                tx_deposit(1, 1, 5.0),
-                tx_withdrawal(1, 2, 5.0),
-                tx_dispute(1, 2),
-                tx_resolve(1, 2),
+                tx_deposit(1, 1, "10.0"),
+                tx_dispute_part(1, 1, "4.0"),
+                tx_dispute_part(1, 1, "7.0"),
             ],
-            &[client(1, 0.0, 0.0, 0.0, false)],
-            &[],
+            &[client(1, "6.0", "4.0", "10.0", false)],
+            &[Message::DisputeAmountExceeded(1, 1, TxType::Deposit)],
         );
     }
 
     #[test]
-    fn test_withdrawal_resolve_unknown_fail() {
+    fn test_deposit_resolve_part() {
         test_process(
             &[
-                tx_deposit(1, 1, 5.0),
-                tx_withdrawal(1, 2, 5.0),
-                tx_resolve(1, 3),
+                tx_deposit(1, 1, "10.0"),
+                tx_dispute_part(1, 1, "4.0"),
+                tx_dispute_part(1, 1, "6.0"),
+                tx_resolve_part(1, 1, "6.0"),
             ],
-            &[client(1, 0.0, 0.0, 0.0, false)],
-            &[Message::UnknownTransaction(1, 3)],
+            &[client(1, "6.0", "4.0", "10.0", false)],
+            &[],
         );
     }
 
     #[test]
-    fn test_withdrawal_resolve_fail() {
+    fn test_deposit_resolve_oldest_part() {
         test_process(
             &[
-                tx_deposit(1, 1, 5.0),
-                tx_withdrawal(1, 2, 5.0),
-                tx_resolve(1, 2),
+                tx_deposit(1, 1, "10.0"),
+                tx_dispute_part(1, 1, "4.0"),
+                tx_dispute_part(1, 1, "6.0"),
+                tx_resolve(1, 1),
             ],
-            &[client(1, 0.0, 0.0, 0.0, false)],
-            &[Message::NotInDispute(1, 2, TxType::Withdrawal)],
+            &[client(1, "4.0", "6.0", "10.0", false)],
+            &[],
         );
     }
 
     #[test]
-    fn test_withdrawal_resolve_dispute() {
+    fn test_deposit_resolve_part_not_found_fail() {
         test_process(
             &[
-                tx_deposit(1, 1, 5.0),
-                tx_withdrawal(1, 2, 5.0),
-                tx_dispute(1, 2),
-                tx_resolve(1, 2),
-                tx_dispute(1, 2),
+                tx_deposit(1, 1, "10.0"),
+                tx_dispute_part(1, 1, "4.0"),
+                tx_resolve_part(1, 1, "5.0"),
             ],
-            &[client(1, 0.0, 0.0, 0.0, false)],
-            &[Message::AlreadyDisputed(1, 2, TxType::Withdrawal)],
+            &[client(1, "6.0", "4.0", "10.0", false)],
+            &[Message::DisputedAmountNotFound(1, 1, TxType::Deposit)],
         );
     }
 
     #[test]
-    fn test_withdrawal_chargeback() {
+    fn test_deposit_chargeback_part() {
         test_process(
             &[
-                tx_deposit(1, 1, 5.0),
-                tx_withdrawal(1, 2, 5.0),
-                tx_dispute(1, 2),
-                tx_chargeback(1, 2),
+                tx_deposit(1, 1, "10.0"),
+                tx_dispute_part(1, 1, "4.0"),
+                tx_dispute_part(1, 1, "6.0"),
+                tx_chargeback_part(1, 1, "4.0"),
             ],
-            &[client(1, 5.0, 0.0, 5.0, true)],
+            &[client(1, "0.0", "6.0", "6.0", true)],
             &[],
         );
     }
 
     #[test]
-    fn test_withdrawal_chargeback_unknown_fail() {
+    fn test_deposit_dispute_part_limit_fail() {
         test_process(
             &[
-                tx_deposit(1, 1, 5.0),
-                tx_withdrawal(1, 2, 5.0),
-                tx_chargeback(1, 3),
+                tx_deposit(1, 1, "10.0"),
+                tx_dispute_part(1, 1, "4.0"),
+                tx_resolve(1, 1),
+                tx_dispute_part(1, 1, "6.0"),
+                tx_dispute_part(1, 1, "4.0"),
             ],
-            &[client(1, 0.0, 0.0, 0.0, false)],
-            &[Message::UnknownTransaction(1, 3)],
+            &[client(1, "4.0", "6.0", "10.0", false)],
+            &[Message::DisputeLimitReached(1, 1, TxType::Deposit)],
         );
     }
 
     #[test]
-    fn test_withdrawal_dispute_withdrawal_fail() {
+    fn test_withdrawal_dispute_part() {
         test_process(
             &[
-                tx_deposit(1, 1, 5.0),
-                tx_withdrawal(1, 2, 5.0),
-                tx_dispute(1, 2),
-                tx_withdrawal(1, 3, 5.0),
+                tx_deposit(1, 1, "10.0"),
+                tx_withdrawal(1, 2, "8.0"),
+                tx_dispute_part(1, 2, "3.0"),
+                tx_chargeback_part(1, 2, "3.0"),
             ],
-            &[client(1, 5.0, -5.0, 0.0, false)],
-            &[Message::NotEnoughFunds(1, 3, TxType::Withdrawal)],
+            &[client(1, "5.0", "0.0", "5.0", true)],
+            &[],
         );
     }
 
-    mod helper {
-        use crate::{
-            client::Client, common::*, message::Message, process::process, transaction::*,
-        };
-
-        pub fn test_process(
-            transactions: &[Tx],
-            expected_clients: &[Client],
-            expected_messages: &[Message],
-        ) {
-            let mut messages = vec![];
-            let clients = process(transactions, &mut messages);
+    #[test]
+    fn test_deposit_chargeback() {
+        test_process(
+            &[
+                tx_deposit(1, 1, "5.0"),
+                tx_dispute(1, 1),
+                tx_chargeback(1, 1),
+            ],
+            &[client(1, "0.0", "0.0", "0.0", true)],
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_deposit_chargeback_unknown_fail() {
+        test_process(
+            &[tx_deposit(1, 1, "5.0"), tx_chargeback(1, 2)],
+            &[client(1, "5.0", "0.0", "5.0", false)],
+            &[Message::UnknownTransaction(1, 2)],
+        );
+    }
+
+    #[test]
+    fn test_account_locked() {
+        test_process(
+            &[
+                tx_deposit(1, 1, "5.0"),
+                tx_dispute(1, 1),
+                tx_chargeback(1, 1),
+                tx_deposit(1, 2, "5.0"),
+            ],
+            &[client(1, "0.0", "0.0", "0.0", true)],
+            &[Message::AccountIsLocked(1, 2, TxType::Deposit)],
+        );
+    }
+
+    /// Client 1 is locked by the chargeback of tx 1 while tx 2 is still in dispute.
+    fn locked_transactions() -> Vec<Tx> {
+        vec![
+            tx_deposit(1, 1, "5.0"),
+            tx_deposit(1, 2, "3.0"),
+            tx_dispute(1, 1),
+            tx_dispute(1, 2),
+            tx_chargeback(1, 1),
+            tx_resolve(1, 2),
+            tx_deposit(1, 3, "2.0"),
+            tx_withdrawal(1, 4, "1.0"),
+        ]
+    }
+
+    #[test]
+    fn test_lock_full() {
+        test_process_config(
+            &lock_config(LockPolicy::Full),
+            &locked_transactions(),
+            &[client(1, "0.0", "3.0", "3.0", true)],
+            &[
+                Message::AccountIsLocked(1, 2, TxType::Resolve),
+                Message::AccountIsLocked(1, 3, TxType::Deposit),
+                Message::AccountIsLocked(1, 4, TxType::Withdrawal),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_lock_debit_only() {
+        test_process_config(
+            &lock_config(LockPolicy::DebitOnly),
+            &locked_transactions(),
+            &[client(1, "5.0", "0.0", "5.0", true)],
+            &[Message::AccountIsLocked(1, 4, TxType::Withdrawal)],
+        );
+    }
+
+    #[test]
+    fn test_lock_none() {
+        test_process_config(
+            &lock_config(LockPolicy::None),
+            &locked_transactions(),
+            &[client(1, "4.0", "0.0", "4.0", true)],
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_lock_debit_only_frozen() {
+        test_process_config(
+            &Config {
+                lock_policy: LockPolicy::DebitOnly,
+                ..admin_config()
+            },
+            &[
+                tx_deposit(1, 1, "5.0"),
+                tx_admin(TxType::Freeze, 1, 2, "investigation"),
+                tx_deposit(1, 3, "1.0"),
+            ],
+            &[client(1, "5.0", "0.0", "5.0", true)],
+            &[Message::AccountIsLocked(1, 3, TxType::Deposit)],
+        );
+    }
+
+    #[test]
+    fn test_admin_not_allowed_fail() {
+        test_process(
+            &[
+                tx_deposit(1, 1, "5.0"),
+                tx_admin(TxType::Freeze, 1, 2, "fraud"),
+            ],
+            &[client(1, "5.0", "0.0", "5.0", false)],
+            &[Message::AdminNotAllowed(1, 2, TxType::Freeze)],
+        );
+    }
+
+    #[test]
+    fn test_unlock_after_chargeback() {
+        test_process_config(
+            &admin_config(),
+            &[
+                tx_deposit(1, 1, "5.0"),
+                tx_deposit(1, 2, "3.0"),
+                tx_dispute(1, 1),
+                tx_chargeback(1, 1),
+                tx_admin(TxType::Unlock, 1, 3, "chargeback_settled"),
+                tx_withdrawal(1, 4, "1.0"),
+            ],
+            &[client(1, "2.0", "0.0", "2.0", false)],
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_freeze_unlock() {
+        test_process_config(
+            &admin_config(),
+            &[
+                tx_deposit(1, 1, "5.0"),
+                tx_admin(TxType::Freeze, 1, 2, "investigation"),
+                tx_deposit(1, 3, "1.0"),
+                tx_admin(TxType::Unlock, 1, 4, "investigation_closed"),
+                tx_deposit(1, 5, "1.0"),
+            ],
+            &[client(1, "6.0", "0.0", "6.0", false)],
+            &[Message::AccountIsLocked(1, 3, TxType::Deposit)],
+        );
+    }
+
+    #[test]
+    fn test_close_unlock_fail() {
+        test_process_config(
+            &admin_config(),
+            &[
+                tx_deposit(1, 1, "5.0"),
+                tx_admin(TxType::Close, 1, 2, "client_request"),
+                tx_deposit(1, 3, "1.0"),
+                tx_admin(TxType::Unlock, 1, 4, "client_request"),
+            ],
+            &[client(1, "5.0", "0.0", "5.0", true)],
+            &[
+                Message::AccountIsClosed(1, 3, TxType::Deposit),
+                Message::AccountIsClosed(1, 4, TxType::Unlock),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_unlock_active_fail() {
+        test_process_config(
+            &admin_config(),
+            &[
+                tx_deposit(1, 1, "5.0"),
+                tx_admin(TxType::Unlock, 1, 2, "mistake"),
+            ],
+            &[client(1, "5.0", "0.0", "5.0", false)],
+            &[Message::AccountNotLocked(1, 2, TxType::Unlock)],
+        );
+    }
+
+    #[test]
+    fn test_admin_history() {
+        let mut engine = Engine::with_config(admin_config());
+        let mut messages = vec![];
+
+        for tx in &[
+            tx_deposit(1, 1, "5.0"),
+            tx_admin(TxType::Freeze, 1, 2, "investigation"),
+            tx_admin(TxType::Unlock, 1, 3, "investigation_closed"),
+            tx_admin(TxType::Unlock, 1, 4, "mistake"),
+        ] {
+            engine.process(tx, &mut messages);
+        }
+
+        let operations: Vec<_> = engine.clients[&1]
+            .operations
+            .iter()
+            .map(|tx| (tx.ty, tx.tx_id, tx.reason()))
+            .collect();
+
+        assert_eq!(
+            vec![
+                (TxType::Freeze, 2, Some("investigation")),
+                (TxType::Unlock, 3, Some("investigation_closed")),
+            ],
+            operations
+        );
+    }
+
+    #[test]
+    fn test_withdrawal_dispute() {
+        test_process(
+            &[
+                tx_deposit(1, 1, "5.0"),
+                tx_withdrawal(1, 2, "5.0"),
+                tx_dispute(1, 2),
+            ],
+            &[client(1, "5.0", "-5.0", "0.0", false)],
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_withdrawal_dispute_unknown_fail() {
+        test_process(
+            &[
+                tx_deposit(1, 1, "5.0"),
+                tx_withdrawal(1, 2, "5.0"),
+                tx_dispute(1, 3),
+            ],
+            &[client(1, "0.0", "0.0", "0.0", false)],
+            &[Message::UnknownTransaction(1, 3)],
+        );
+    }
+
+    #[test]
+    fn test_withdrawal_resolve() {
+        test_process(
+            &[
+                tx_deposit(1, 1, "5.0"),
+                tx_withdrawal(1, 2, "5.0"),
+                tx_dispute(1, 2),
+                tx_resolve(1, 2),
+            ],
+            &[client(1, "0.0", "0.0", "0.0", false)],
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_withdrawal_resolve_unknown_fail() {
+        test_process(
+            &[
+                tx_deposit(1, 1, "5.0"),
+                tx_withdrawal(1, 2, "5.0"),
+                tx_resolve(1, 3),
+            ],
+            &[client(1, "0.0", "0.0", "0.0", false)],
+            &[Message::UnknownTransaction(1, 3)],
+        );
+    }
+
+    #[test]
+    fn test_withdrawal_resolve_fail() {
+        test_process(
+            &[
+                tx_deposit(1, 1, "5.0"),
+                tx_withdrawal(1, 2, "5.0"),
+                tx_resolve(1, 2),
+            ],
+            &[client(1, "0.0", "0.0", "0.0", false)],
+            &[Message::NotInDispute(1, 2, TxType::Withdrawal)],
+        );
+    }
+
+    #[test]
+    fn test_withdrawal_resolve_dispute() {
+        test_process(
+            &[
+                tx_deposit(1, 1, "5.0"),
+                tx_withdrawal(1, 2, "5.0"),
+                tx_dispute(1, 2),
+                tx_resolve(1, 2),
+                tx_dispute(1, 2),
+            ],
+            &[client(1, "0.0", "0.0", "0.0", false)],
+            &[Message::DisputeLimitReached(1, 2, TxType::Withdrawal)],
+        );
+    }
+
+    #[test]
+    fn test_withdrawal_chargeback() {
+        test_process(
+            &[
+                tx_deposit(1, 1, "5.0"),
+                tx_withdrawal(1, 2, "5.0"),
+                tx_dispute(1, 2),
+                tx_chargeback(1, 2),
+            ],
+            &[client(1, "5.0", "0.0", "5.0", true)],
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_withdrawal_chargeback_unknown_fail() {
+        test_process(
+            &[
+                tx_deposit(1, 1, "5.0"),
+                tx_withdrawal(1, 2, "5.0"),
+                tx_chargeback(1, 3),
+            ],
+            &[client(1, "0.0", "0.0", "0.0", false)],
+            &[Message::UnknownTransaction(1, 3)],
+        );
+    }
+
+    #[test]
+    fn test_withdrawal_dispute_withdrawal_fail() {
+        test_process(
+            &[
+                tx_deposit(1, 1, "5.0"),
+                tx_withdrawal(1, 2, "5.0"),
+                tx_dispute(1, 2),
+                tx_withdrawal(1, 3, "5.0"),
+            ],
+            &[client(1, "5.0", "-5.0", "0.0", false)],
+            &[Message::NotEnoughFunds(1, 3, TxType::Withdrawal)],
+        );
+    }
+
+    #[test]
+    fn test_deposit_exact_sum() {
+        test_process(
+            &[
+                tx_deposit(1, 1, "0.1"),
+                tx_deposit(1, 2, "0.2"),
+                tx_withdrawal(1, 3, "0.3"),
+            ],
+            &[client(1, "0", "0", "0", false)],
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_deposit_large_balance() {
+        test_process(
+            &[tx_deposit(1, 1, "16777216"), tx_deposit(1, 2, "0.0001")],
+            &[client(1, "16777216.0001", "0", "16777216.0001", false)],
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_deposit_overflow_fail() {
+        test_process(
+            &[
+                tx_deposit(1, 1, "900000000000000"),
+                tx_deposit(1, 2, "900000000000000"),
+            ],
+            &[client(1, "900000000000000", "0", "900000000000000", false)],
+            &[Message::ValueOverflow(1, 2, TxType::Deposit)],
+        );
+    }
+
+    #[test]
+    fn test_dispute_overflow_fail() {
+        for policy in &[
+            WithdrawalDisputePolicy::Legacy,
+            WithdrawalDisputePolicy::HoldCredit,
+        ] {
+            test_process_config(
+                &withdrawal_config(*policy),
+                &[
+                    tx_deposit(1, 1, "922337203685477"),
+                    tx_withdrawal(1, 2, "100"),
+                    tx_deposit(1, 3, "100"),
+                    tx_dispute(1, 2),
+                    tx_resolve(1, 2),
+                ],
+                &[client(1, "922337203685477", "0", "922337203685477", false)],
+                &[
+                    Message::ValueOverflow(1, 2, TxType::Dispute),
+                    Message::NotInDispute(1, 2, TxType::Withdrawal),
+                ],
+            );
+        }
+    }
+
+    #[test]
+    fn test_transaction_same_id_other_client_fail() {
+        test_process(
+            &[tx_deposit(1, 1, "5.0"), tx_deposit(2, 1, "2.0")],
+            &[
+                client(1, "5.0", "0.0", "5.0", false),
+                client(2, "0.0", "0.0", "0.0", false),
+            ],
+            &[Message::TransactionExistForOtherClient(
+                2,
+                1,
+                TxType::Deposit,
+            )],
+        );
+    }
+
+    #[test]
+    fn test_transaction_same_id_other_client_rejected_fail() {
+        test_process(
+            &[tx_withdrawal(1, 1, "5.0"), tx_deposit(2, 1, "2.0")],
+            &[
+                client(1, "0.0", "0.0", "0.0", false),
+                client(2, "0.0", "0.0", "0.0", false),
+            ],
+            &[
+                Message::NotEnoughFunds(1, 1, TxType::Withdrawal),
+                Message::TransactionExistForOtherClient(2, 1, TxType::Deposit),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_dispute_other_client_fail() {
+        test_process(
+            &[
+                tx_deposit(1, 1, "5.0"),
+                tx_deposit(2, 2, "2.0"),
+                tx_dispute(2, 1),
+                tx_resolve(2, 1),
+                tx_chargeback(2, 1),
+            ],
+            &[
+                client(1, "5.0", "0.0", "5.0", false),
+                client(2, "2.0", "0.0", "2.0", false),
+            ],
+            &[
+                Message::TransactionOfOtherClient(2, 1, TxType::Dispute),
+                Message::TransactionOfOtherClient(2, 1, TxType::Resolve),
+                Message::TransactionOfOtherClient(2, 1, TxType::Chargeback),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_transfer() {
+        test_process(
+            &[tx_deposit(1, 1, "10.0"), tx_transfer(1, 2, 2, "4.0")],
+            &[
+                client(1, "6.0", "0.0", "6.0", false),
+                client(2, "4.0", "0.0", "4.0", false),
+            ],
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_transfer_not_enough_funds_fail() {
+        test_process(
+            &[tx_deposit(1, 1, "3.0"), tx_transfer(1, 2, 2, "4.0")],
+            &[
+                client(1, "3.0", "0.0", "3.0", false),
+                client(2, "0.0", "0.0", "0.0", false),
+            ],
+            &[Message::NotEnoughFunds(1, 2, TxType::Transfer)],
+        );
+    }
+
+    #[test]
+    fn test_transfer_same_client_fail() {
+        test_process(
+            &[tx_deposit(1, 1, "10.0"), tx_transfer(1, 2, 1, "4.0")],
+            &[client(1, "10.0", "0.0", "10.0", false)],
+            &[Message::InvalidDestination(1, 2, TxType::Transfer)],
+        );
+    }
+
+    #[test]
+    fn test_transfer_destination_locked_fail() {
+        test_process(
+            &[
+                tx_deposit(1, 1, "10.0"),
+                tx_deposit(2, 2, "1.0"),
+                tx_dispute(2, 2),
+                tx_chargeback(2, 2),
+                tx_transfer(1, 3, 2, "4.0"),
+            ],
+            &[
+                client(1, "10.0", "0.0", "10.0", false),
+                client(2, "0.0", "0.0", "0.0", true),
+            ],
+            &[Message::AccountIsLocked(2, 3, TxType::Transfer)],
+        );
+    }
+
+    #[test]
+    fn test_transfer_locked_debit_only() {
+        test_process_config(
+            &lock_config(LockPolicy::DebitOnly),
+            &[
+                tx_deposit(1, 1, "10.0"),
+                tx_deposit(1, 2, "1.0"),
+                tx_dispute(1, 2),
+                tx_chargeback(1, 2),
+                tx_transfer(1, 3, 2, "4.0"),
+                tx_deposit(2, 4, "3.0"),
+                tx_transfer(2, 5, 1, "2.0"),
+            ],
+            &[
+                client(1, "12.0", "0.0", "12.0", true),
+                client(2, "1.0", "0.0", "1.0", false),
+            ],
+            &[Message::AccountIsLocked(1, 3, TxType::Transfer)],
+        );
+    }
+
+    #[test]
+    fn test_transfer_dispute() {
+        test_process(
+            &[
+                tx_deposit(1, 1, "10.0"),
+                tx_transfer(1, 2, 2, "4.0"),
+                tx_dispute(1, 2),
+            ],
+            &[
+                client(1, "6.0", "0.0", "6.0", false),
+                client(2, "0.0", "4.0", "4.0", false),
+            ],
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_transfer_resolve() {
+        test_process(
+            &[
+                tx_deposit(1, 1, "10.0"),
+                tx_transfer(1, 2, 2, "4.0"),
+                tx_dispute(1, 2),
+                tx_resolve(1, 2),
+            ],
+            &[
+                client(1, "6.0", "0.0", "6.0", false),
+                client(2, "4.0", "0.0", "4.0", false),
+            ],
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_transfer_chargeback() {
+        test_process(
+            &[
+                tx_deposit(1, 1, "10.0"),
+                tx_transfer(1, 2, 2, "4.0"),
+                tx_dispute_part(1, 2, "3.0"),
+                tx_chargeback(1, 2),
+            ],
+            &[
+                client(1, "9.0", "0.0", "9.0", false),
+                client(2, "1.0", "0.0", "1.0", true),
+            ],
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_transfer_dispute_spent_reject_fail() {
+        test_process_config(
+            &config(DisputePolicy::Reject),
+            &[
+                tx_deposit(1, 1, "10.0"),
+                tx_transfer(1, 2, 2, "4.0"),
+                tx_withdrawal(2, 3, "2.0"),
+                tx_dispute(1, 2),
+            ],
+            &[
+                client(1, "6.0", "0.0", "6.0", false),
+                client(2, "2.0", "0.0", "2.0", false),
+            ],
+            &[Message::NotEnoughFundsForDispute(2, 2, TxType::Transfer)],
+        );
+    }
+
+    #[test]
+    fn test_transfer_dispute_by_destination_fail() {
+        test_process(
+            &[
+                tx_deposit(1, 1, "10.0"),
+                tx_transfer(1, 2, 2, "4.0"),
+                tx_dispute(2, 2),
+            ],
+            &[
+                client(1, "6.0", "0.0", "6.0", false),
+                client(2, "4.0", "0.0", "4.0", false),
+            ],
+            &[Message::TransactionOfOtherClient(2, 2, TxType::Dispute)],
+        );
+    }
+
+    #[test]
+    fn test_transfer_rejected_id_reused() {
+        test_process(
+            &[
+                tx_transfer(1, 1, 2, "4.0"),
+                tx_deposit(1, 1, "10.0"),
+                tx_dispute(1, 1),
+            ],
+            &[
+                client(1, "0.0", "10.0", "10.0", false),
+                client(2, "0.0", "0.0", "0.0", false),
+            ],
+            &[Message::NotEnoughFunds(1, 1, TxType::Transfer)],
+        );
+    }
+
+    #[test]
+    fn test_withdrawal_fee() {
+        test_process_config(
+            &fee_config(r#"{"withdrawal": {"flat": "0.5"}}"#),
+            &[tx_deposit(1, 1, "10.0"), tx_withdrawal(1, 2, "4.0")],
+            &[client(1, "5.5", "0.0", "5.5", false)],
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_withdrawal_fee_not_enough_funds_fail() {
+        test_process_config(
+            &fee_config(r#"{"withdrawal": {"percentage": "1"}}"#),
+            &[tx_deposit(1, 1, "10.0"), tx_withdrawal(1, 2, "10.0")],
+            &[client(1, "10.0", "0.0", "10.0", false)],
+            &[Message::NotEnoughFunds(1, 2, TxType::Withdrawal)],
+        );
+    }
+
+    #[test]
+    fn test_withdrawal_tiered_fee() {
+        test_process_config(
+            &fee_config(
+                r#"{"withdrawal": {"tiered": [
+                    {"from": "5", "fee": {"flat": "0.1"}},
+                    {"from": "100", "fee": {"percentage": "0.5"}}
+                ]}}"#,
+            ),
+            &[
+                tx_deposit(1, 1, "1000.0"),
+                tx_withdrawal(1, 2, "1.0"),
+                tx_withdrawal(1, 3, "10.0"),
+                tx_withdrawal(1, 4, "200.0"),
+            ],
+            &[client(1, "787.9", "0.0", "787.9", false)],
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_chargeback_fee() {
+        test_process_config(
+            &fee_config(r#"{"chargeback": {"flat": "15"}}"#),
+            &[
+                tx_deposit(1, 1, "10.0"),
+                tx_deposit(1, 2, "5.0"),
+                tx_dispute(1, 2),
+                tx_chargeback(1, 2),
+            ],
+            &[client(1, "-5.0", "0.0", "-5.0", true)],
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_transfer_chargeback_fee() {
+        test_process_config(
+            &fee_config(r#"{"chargeback": {"percentage": "10"}}"#),
+            &[
+                tx_deposit(1, 1, "10.0"),
+                tx_transfer(1, 2, 2, "4.0"),
+                tx_dispute(1, 2),
+                tx_chargeback(1, 2),
+            ],
+            &[
+                client(1, "10.0", "0.0", "10.0", false),
+                client(2, "-0.4", "0.0", "-0.4", true),
+            ],
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_fee_history() {
+        let mut engine = Engine::with_config(fee_config(
+            r#"{"withdrawal": {"flat": "0.5"}, "chargeback": {"flat": "2"}}"#,
+        ));
+        let mut messages = vec![];
+
+        for tx in &[
+            tx_deposit(1, 1, "10.0"),
+            tx_withdrawal(1, 2, "4.0"),
+            tx_withdrawal(1, 3, "6.0"),
+            tx_dispute(1, 1),
+            tx_chargeback(1, 1),
+        ] {
+            engine.process(tx, &mut messages);
+        }
+
+        let fees: Vec<_> = engine.clients[&1]
+            .operations
+            .iter()
+            .map(|tx| (tx.ty, tx.tx_id, tx.amount))
+            .collect();
+
+        assert_eq!(
+            vec![
+                (TxType::Fee, 2, value("0.5")),
+                (TxType::Fee, 1, value("2.0")),
+            ],
+            fees
+        );
+        assert_eq!(
+            vec![
+                Message::NotEnoughFunds(1, 3, TxType::Withdrawal),
+                Message::NegativeAvailable(1, 1, TxType::Deposit),
+            ],
+            messages
+        );
+    }
+
+    #[test]
+    fn test_fee_transaction_fail() {
+        test_process(
+            &[
+                tx_deposit(1, 1, "10.0"),
+                Tx::new(TxType::Fee, 1, 2, value("1.0")),
+            ],
+            &[client(1, "10.0", "0.0", "10.0", false)],
+            &[Message::SystemTransaction(1, 2, TxType::Fee)],
+        );
+    }
+
+    #[test]
+    fn test_withdrawal_credit() {
+        test_process_credit(
+            &[(1, "10.0")],
+            &[tx_deposit(1, 1, "5.0"), tx_withdrawal(1, 2, "12.0")],
+            &[credit(client(1, "-7.0", "0.0", "-7.0", false), "3.0")],
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_withdrawal_credit_exceeded_fail() {
+        test_process_credit(
+            &[(1, "10.0")],
+            &[
+                tx_deposit(1, 1, "5.0"),
+                tx_withdrawal(1, 2, "12.0"),
+                tx_withdrawal(1, 3, "3.5"),
+            ],
+            &[credit(client(1, "-7.0", "0.0", "-7.0", false), "3.0")],
+            &[Message::CreditLimitExceeded(1, 3, TxType::Withdrawal)],
+        );
+    }
+
+    #[test]
+    fn test_transfer_credit() {
+        test_process_credit(
+            &[(1, "10.0"), (2, "1.0")],
+            &[tx_transfer(1, 1, 2, "4.0"), tx_transfer(2, 2, 3, "6.0")],
+            &[
+                credit(client(1, "-4.0", "0.0", "-4.0", false), "6.0"),
+                credit(client(2, "4.0", "0.0", "4.0", false), "1.0"),
+                client(3, "0.0", "0.0", "0.0", false),
+            ],
+            &[Message::CreditLimitExceeded(2, 2, TxType::Transfer)],
+        );
+    }
+
+    #[test]
+    fn test_currency_balances() {
+        test_process(
+            &[
+                in_currency(tx_deposit(1, 1, "5.0"), "USD"),
+                in_currency(tx_deposit(1, 2, "3.0"), "EUR"),
+                in_currency(tx_withdrawal(1, 3, "4.0"), "EUR"),
+                in_currency(tx_withdrawal(1, 4, "1.0"), "USD"),
+                tx_deposit(1, 5, "2.0"),
+            ],
+            &[
+                client(1, "2.0", "0.0", "2.0", false),
+                currency(client(1, "3.0", "0.0", "3.0", false), "EUR"),
+                currency(client(1, "4.0", "0.0", "4.0", false), "USD"),
+            ],
+            &[Message::NotEnoughFunds(1, 3, TxType::Withdrawal)],
+        );
+    }
+
+    #[test]
+    fn test_currency_dispute() {
+        test_process(
+            &[
+                in_currency(tx_deposit(1, 1, "5.0"), "EUR"),
+                tx_deposit(1, 2, "1.0"),
+                tx_dispute(1, 1),
+                in_currency(tx_dispute(1, 2), "EUR"),
+                in_currency(tx_chargeback(1, 1), "EUR"),
+            ],
+            &[
+                client(1, "1.0", "0.0", "1.0", true),
+                currency(client(1, "0.0", "0.0", "0.0", true), "EUR"),
+            ],
+            &[Message::CurrencyMismatch(1, 2, TxType::Deposit)],
+        );
+    }
+
+    #[test]
+    fn test_currency_transfer() {
+        test_process(
+            &[
+                in_currency(tx_deposit(1, 1, "5.0"), "EUR"),
+                in_currency(tx_transfer(1, 2, 2, "2.0"), "EUR"),
+                tx_dispute(1, 2),
+                in_currency(tx_transfer(1, 3, 2, "1.0"), "GBP"),
+            ],
+            &[
+                currency(client(1, "3.0", "0.0", "3.0", false), "EUR"),
+                currency(client(2, "0.0", "2.0", "2.0", false), "EUR"),
+            ],
+            &[Message::NotEnoughFunds(1, 3, TxType::Transfer)],
+        );
+    }
+
+    #[test]
+    fn test_currency_account() {
+        let mut engine = Engine::new();
+        let mut messages = vec![];
+
+        engine
+            .set_profile(&Profile {
+                currency: Some("USD".to_string()),
+                ..Profile::new(1)
+            })
+            .unwrap();
+
+        for tx in &[
+            in_currency(tx_deposit(1, 1, "5.0"), "USD"),
+            tx_deposit(1, 2, "1.0"),
+            tx_dispute(1, 1),
+        ] {
+            engine.process(tx, &mut messages);
+        }
+
+        assert!(messages.is_empty());
+        assert_eq!(
+            vec![currency(client(1, "1.0", "5.0", "6.0", false), "USD")],
+            engine.clients()
+        );
+    }
+
+    #[test]
+    fn test_exchange() {
+        let mut engine = Engine::with_config(rates_config());
+        let mut messages = vec![];
+
+        for tx in &[
+            in_currency(tx_deposit(1, 1, "10.0"), "EUR"),
+            tx_exchange(1, 2, "4.0", "EUR", "USD", Some("2021-01-15")),
+            tx_exchange(1, 3, "2.0", "EUR", "USD", None),
+            tx_exchange(1, 4, "5.0", "EUR", "USD", None),
+            tx_exchange(1, 5, "1.0", "EUR", "GBP", None),
+            tx_exchange(1, 6, "1.0", "USD", "EUR", None),
+        ] {
+            engine.process(tx, &mut messages);
+        }
+
+        assert_eq!(
+            vec![
+                Message::NotEnoughFunds(1, 4, TxType::Exchange),
+                Message::RateNotFound(1, 5, TxType::Exchange),
+                Message::RateNotFound(1, 6, TxType::Exchange),
+            ],
+            messages
+        );
+        assert_eq!(
+            vec![
+                currency(client(1, "4.0", "0.0", "4.0", false), "EUR"),
+                currency(client(1, "7.0", "0.0", "7.0", false), "USD"),
+            ],
+            engine.clients()
+        );
+        assert_eq!(Some(value("1.2")), engine.transaction(2).unwrap().rate());
+        assert_eq!(Some(value("1.1")), engine.transaction(3).unwrap().rate());
+    }
+
+    #[test]
+    fn test_exchange_dispute() {
+        test_process_config(
+            &rates_config(),
+            &[
+                in_currency(tx_deposit(1, 1, "10.0"), "EUR"),
+                tx_exchange(1, 2, "4.0", "EUR", "USD", Some("2021-01-15")),
+                tx_dispute_part(1, 2, "1.0"),
+                tx_dispute(1, 2),
+                tx_resolve(1, 2),
+            ],
+            &[
+                currency(client(1, "6.0", "0.0", "6.0", false), "EUR"),
+                currency(client(1, "4.8", "0.0", "4.8", false), "USD"),
+            ],
+            &[Message::PartialExchangeDispute(1, 2, TxType::Exchange)],
+        );
+    }
+
+    #[test]
+    fn test_exchange_chargeback() {
+        test_process_config(
+            &rates_config(),
+            &[
+                in_currency(tx_deposit(1, 1, "10.0"), "EUR"),
+                tx_exchange(1, 2, "4.0", "EUR", "USD", Some("2021-01-15")),
+                tx_dispute(1, 2),
+                in_currency(tx_chargeback(1, 2), "USD"),
+                in_currency(tx_chargeback(1, 2), "EUR"),
+            ],
+            &[
+                currency(client(1, "10.0", "0.0", "10.0", true), "EUR"),
+                currency(client(1, "0.0", "0.0", "0.0", true), "USD"),
+            ],
+            &[Message::CurrencyMismatch(1, 2, TxType::Exchange)],
+        );
+    }
+
+    #[test]
+    fn test_exchange_chargeback_fee() {
+        let config = Config {
+            fees: fee_config(r#"{"chargeback": {"percentage": "10"}}"#).fees,
+            ..rates_config()
+        };
+
+        test_process_config(
+            &config,
+            &[
+                in_currency(tx_deposit(1, 1, "10.0"), "EUR"),
+                tx_exchange(1, 2, "4.0", "EUR", "USD", Some("2021-01-15")),
+                tx_dispute(1, 2),
+                in_currency(tx_chargeback(1, 2), "EUR"),
+            ],
+            &[
+                currency(client(1, "10.0", "0.0", "10.0", true), "EUR"),
+                currency(client(1, "-0.48", "0.0", "-0.48", true), "USD"),
+            ],
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_strict_unknown_client_fail() {
+        test_process_config(
+            &strict_config(),
+            &[tx_deposit(1, 1, "5.0"), tx_withdrawal(1, 2, "1.0")],
+            &[],
+            &[
+                Message::UnknownClient(1, 1, TxType::Deposit),
+                Message::UnknownClient(1, 2, TxType::Withdrawal),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_strict_profile() {
+        let mut engine = Engine::with_config(strict_config());
+        let mut messages = vec![];
+
+        engine.set_profile(&Profile::new(1)).unwrap();
+        engine
+            .set_profile(&Profile {
+                status: Some(AccountStatus::Frozen),
+                ..Profile::new(2)
+            })
+            .unwrap();
+
+        for tx in &[
+            tx_deposit(1, 1, "5.0"),
+            tx_deposit(2, 2, "5.0"),
+            tx_transfer(1, 3, 3, "1.0"),
+            tx_deposit(3, 4, "5.0"),
+        ] {
+            engine.process(tx, &mut messages);
+        }
+
+        assert_eq!(
+            vec![
+                Message::AccountIsLocked(2, 2, TxType::Deposit),
+                Message::UnknownClient(3, 3, TxType::Transfer),
+                Message::UnknownClient(3, 4, TxType::Deposit),
+            ],
+            messages
+        );
+        assert_eq!(
+            vec![
+                client(1, "5.0", "0.0", "5.0", false),
+                client(2, "0.0", "0.0", "0.0", true),
+            ],
+            engine.clients()
+        );
+    }
+
+    #[test]
+    fn test_clients_sorted() {
+        test_process(
+            &[
+                tx_deposit(3, 1, "1.0"),
+                tx_deposit(1, 2, "2.0"),
+                tx_withdrawal(2, 3, "3.0"),
+                tx_deposit(10, 4, "4.0"),
+            ],
+            &[
+                client(1, "2.0", "0.0", "2.0", false),
+                client(2, "0.0", "0.0", "0.0", false),
+                client(3, "1.0", "0.0", "1.0", false),
+                client(10, "4.0", "0.0", "4.0", false),
+            ],
+            &[Message::NotEnoughFunds(2, 3, TxType::Withdrawal)],
+        );
+    }
+
+    #[test]
+    fn test_clients_unsorted() {
+        let mut engine = Engine::new();
+
+        for id in (1..=100).rev() {
+            engine.process(&tx_deposit(id, id as TxId, "1.0"), &mut vec![]);
+        }
+
+        let mut clients = engine.sorted_clients(SortOrder::None);
+
+        assert_eq!(100, clients.len());
+
+        clients.sort_by_key(|c| c.id);
+
+        assert_eq!(engine.sorted_clients(SortOrder::Client), clients);
+    }
+
+    #[test]
+    fn test_withdrawal_dispute_legacy() {
+        test_process_config(
+            &withdrawal_config(WithdrawalDisputePolicy::Legacy),
+            &[
+                tx_deposit(1, 1, "10.0"),
+                tx_withdrawal(1, 2, "4.0"),
+                tx_dispute(1, 2),
+            ],
+            &[client(1, "10.0", "-4.0", "6.0", false)],
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_withdrawal_resolve_legacy() {
+        test_process_config(
+            &withdrawal_config(WithdrawalDisputePolicy::Legacy),
+            &[
+                tx_deposit(1, 1, "10.0"),
+                tx_withdrawal(1, 2, "4.0"),
+                tx_dispute(1, 2),
+                tx_resolve(1, 2),
+            ],
+            &[client(1, "6.0", "0.0", "6.0", false)],
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_withdrawal_chargeback_legacy() {
+        test_process_config(
+            &withdrawal_config(WithdrawalDisputePolicy::Legacy),
+            &[
+                tx_deposit(1, 1, "10.0"),
+                tx_withdrawal(1, 2, "4.0"),
+                tx_dispute(1, 2),
+                tx_chargeback(1, 2),
+            ],
+            &[client(1, "10.0", "0.0", "10.0", true)],
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_withdrawal_dispute_hold_credit() {
+        test_process_config(
+            &withdrawal_config(WithdrawalDisputePolicy::HoldCredit),
+            &[
+                tx_deposit(1, 1, "10.0"),
+                tx_withdrawal(1, 2, "4.0"),
+                tx_dispute(1, 2),
+            ],
+            &[client(1, "6.0", "4.0", "10.0", false)],
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_withdrawal_resolve_hold_credit() {
+        test_process_config(
+            &withdrawal_config(WithdrawalDisputePolicy::HoldCredit),
+            &[
+                tx_deposit(1, 1, "10.0"),
+                tx_withdrawal(1, 2, "4.0"),
+                tx_dispute(1, 2),
+                tx_resolve(1, 2),
+            ],
+            &[client(1, "6.0", "0.0", "6.0", false)],
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_withdrawal_chargeback_hold_credit() {
+        test_process_config(
+            &withdrawal_config(WithdrawalDisputePolicy::HoldCredit),
+            &[
+                tx_deposit(1, 1, "10.0"),
+                tx_withdrawal(1, 2, "4.0"),
+                tx_dispute(1, 2),
+                tx_chargeback(1, 2),
+            ],
+            &[client(1, "10.0", "0.0", "10.0", true)],
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_withdrawal_dispute_disallow() {
+        test_process_config(
+            &withdrawal_config(WithdrawalDisputePolicy::Disallow),
+            &[
+                tx_deposit(1, 1, "10.0"),
+                tx_withdrawal(1, 2, "4.0"),
+                tx_dispute(1, 2),
+            ],
+            &[client(1, "6.0", "0.0", "6.0", false)],
+            &[Message::WithdrawalDisputeNotAllowed(
+                1,
+                2,
+                TxType::Withdrawal,
+            )],
+        );
+    }
+
+    #[test]
+    fn test_withdrawal_resolve_disallow() {
+        test_process_config(
+            &withdrawal_config(WithdrawalDisputePolicy::Disallow),
+            &[
+                tx_deposit(1, 1, "10.0"),
+                tx_withdrawal(1, 2, "4.0"),
+                tx_dispute(1, 2),
+                tx_resolve(1, 2),
+            ],
+            &[client(1, "6.0", "0.0", "6.0", false)],
+            &[
+                Message::WithdrawalDisputeNotAllowed(1, 2, TxType::Withdrawal),
+                Message::NotInDispute(1, 2, TxType::Withdrawal),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_withdrawal_chargeback_disallow() {
+        test_process_config(
+            &withdrawal_config(WithdrawalDisputePolicy::Disallow),
+            &[
+                tx_deposit(1, 1, "10.0"),
+                tx_withdrawal(1, 2, "4.0"),
+                tx_dispute(1, 2),
+                tx_chargeback(1, 2),
+            ],
+            &[client(1, "6.0", "0.0", "6.0", false)],
+            &[
+                Message::WithdrawalDisputeNotAllowed(1, 2, TxType::Withdrawal),
+                Message::NotInDispute(1, 2, TxType::Withdrawal),
+            ],
+        );
+    }
+
+    mod helper {
+        use crate::{
+            client::ClientSnapshot,
+            common::*,
+            config::{Config, DisputePolicy, LockPolicy, WithdrawalDisputePolicy},
+            fee::FeeSchedule,
+            message::Message,
+            process::Engine,
+            profile::Profile,
+            rate::RateTable,
+            transaction::*,
+            value::Value,
+        };
+
+        pub fn test_process(
+            transactions: &[Tx],
+            expected_clients: &[ClientSnapshot],
+            expected_messages: &[Message],
+        ) {
+            test_process_config(
+                &Config::default(),
+                transactions,
+                expected_clients,
+                expected_messages,
+            );
+        }
+
+        pub fn test_process_config(
+            config: &Config,
+            transactions: &[Tx],
+            expected_clients: &[ClientSnapshot],
+            expected_messages: &[Message],
+        ) {
+            let mut engine = Engine::with_config(config.clone());
+            let mut messages = vec![];
+
+            for tx in transactions {
+                engine.process(tx, &mut messages);
+            }
 
             assert_eq!(expected_messages, messages, "messages");
-            assert_eq!(expected_clients, clients, "clients");
+            assert_eq!(expected_clients, engine.clients(), "clients");
+        }
+
+        pub fn config(dispute_policy: DisputePolicy) -> Config {
+            Config {
+                dispute_policy,
+                ..Default::default()
+            }
+        }
+
+        pub fn withdrawal_config(withdrawal_dispute_policy: WithdrawalDisputePolicy) -> Config {
+            Config {
+                withdrawal_dispute_policy,
+                ..Default::default()
+            }
+        }
+
+        pub fn max_disputes_config(max_disputes: u32) -> Config {
+            Config {
+                max_disputes,
+                ..Default::default()
+            }
         }
 
-        pub fn tx_deposit(client_id: ClientId, tx_id: TxId, amount: Value) -> Tx {
+        pub fn tx_deposit(client_id: ClientId, tx_id: TxId, amount: &str) -> Tx {
             tx(TxType::Deposit, client_id, tx_id, amount)
         }
 
-        pub fn tx_withdrawal(client_id: ClientId, tx_id: TxId, amount: Value) -> Tx {
+        pub fn tx_withdrawal(client_id: ClientId, tx_id: TxId, amount: &str) -> Tx {
             tx(TxType::Withdrawal, client_id, tx_id, amount)
         }
 
+        pub fn tx_transfer(
+            client_id: ClientId,
+            tx_id: TxId,
+            destination: ClientId,
+            amount: &str,
+        ) -> Tx {
+            tx(TxType::Transfer, client_id, tx_id, amount).with_details(TxDetails {
+                destination: Some(destination),
+                ..TxDetails::default()
+            })
+        }
+
         pub fn tx_dispute(client_id: ClientId, tx_id: TxId) -> Tx {
-            tx(TxType::Dispute, client_id, tx_id, 0.0)
+            tx(TxType::Dispute, client_id, tx_id, "0")
         }
 
         pub fn tx_resolve(client_id: ClientId, tx_id: TxId) -> Tx {
-            tx(TxType::Resolve, client_id, tx_id, 0.0)
+            tx(TxType::Resolve, client_id, tx_id, "0")
         }
 
         pub fn tx_chargeback(client_id: ClientId, tx_id: TxId) -> Tx {
-            tx(TxType::Chargeback, client_id, tx_id, 0.0)
+            tx(TxType::Chargeback, client_id, tx_id, "0")
+        }
+
+        pub fn lock_config(lock_policy: LockPolicy) -> Config {
+            Config {
+                lock_policy,
+                ..Default::default()
+            }
+        }
+
+        /// Processes transactions with clients that have credit limits.
+        pub fn test_process_credit(
+            limits: &[(ClientId, &str)],
+            transactions: &[Tx],
+            expected_clients: &[ClientSnapshot],
+            expected_messages: &[Message],
+        ) {
+            let mut engine = Engine::new();
+            let mut messages = vec![];
+
+            for (client, limit) in limits {
+                engine
+                    .set_profile(&Profile {
+                        credit_limit: Some(value(limit)),
+                        ..Profile::new(*client)
+                    })
+                    .unwrap();
+            }
+
+            for tx in transactions {
+                engine.process(tx, &mut messages);
+            }
+
+            assert_eq!(expected_messages, messages, "messages");
+            assert_eq!(expected_clients, engine.clients(), "clients");
+        }
+
+        pub fn credit(client: ClientSnapshot, credit: &str) -> ClientSnapshot {
+            ClientSnapshot {
+                credit: Some(value(credit)),
+                ..client
+            }
+        }
+
+        pub fn in_currency(mut tx: Tx, currency: &str) -> Tx {
+            tx.details_mut().currency = Some(currency.to_string());
+            tx
         }
 
-        fn tx(ty: TxType, client_id: ClientId, tx_id: TxId, amount: Value) -> Tx {
-            Tx {
-                ty,
-                client_id,
-                tx_id,
-                amount,
-                state: Default::default(),
+        pub fn currency(client: ClientSnapshot, currency: &str) -> ClientSnapshot {
+            ClientSnapshot {
+                currency: Some(currency.to_string()),
+                ..client
             }
         }
 
+        pub fn rates_config() -> Config {
+            let rates = "from,to,rate,valid_from,valid_until\n\
+                         EUR,USD,1.1,,\n\
+                         EUR,USD,1.2,2021-01-01,2021-02-01\n";
+
+            Config {
+                rates: RateTable::read_all(rates.as_bytes()).unwrap(),
+                ..Default::default()
+            }
+        }
+
+        pub fn tx_exchange(
+            client_id: ClientId,
+            tx_id: TxId,
+            amount: &str,
+            from: &str,
+            to: &str,
+            date: Option<&str>,
+        ) -> Tx {
+            tx(TxType::Exchange, client_id, tx_id, amount).with_details(TxDetails {
+                currency: Some(from.to_string()),
+                to_currency: Some(to.to_string()),
+                date: date.map(str::to_string),
+                ..TxDetails::default()
+            })
+        }
+
+        pub fn strict_config() -> Config {
+            Config {
+                strict: true,
+                ..Default::default()
+            }
+        }
+
+        pub fn fee_config(fees: &str) -> Config {
+            Config {
+                fees: FeeSchedule::from_reader(fees.as_bytes()).unwrap(),
+                ..Default::default()
+            }
+        }
+
+        pub fn admin_config() -> Config {
+            Config {
+                admin_operations: true,
+                ..Default::default()
+            }
+        }
+
+        pub fn tx_admin(ty: TxType, client_id: ClientId, tx_id: TxId, reason: &str) -> Tx {
+            Tx::new(ty, client_id, tx_id, Value::ZERO).with_details(TxDetails {
+                reason: Some(reason.to_string()),
+                ..TxDetails::default()
+            })
+        }
+
+        pub fn tx_dispute_part(client_id: ClientId, tx_id: TxId, amount: &str) -> Tx {
+            tx(TxType::Dispute, client_id, tx_id, amount)
+        }
+
+        pub fn tx_resolve_part(client_id: ClientId, tx_id: TxId, amount: &str) -> Tx {
+            tx(TxType::Resolve, client_id, tx_id, amount)
+        }
+
+        pub fn tx_chargeback_part(client_id: ClientId, tx_id: TxId, amount: &str) -> Tx {
+            tx(TxType::Chargeback, client_id, tx_id, amount)
+        }
+
+        fn tx(ty: TxType, client_id: ClientId, tx_id: TxId, amount: &str) -> Tx {
+            Tx::new(ty, client_id, tx_id, value(amount))
+        }
+
         pub fn client(
             client: ClientId,
-            available: Value,
-            held: Value,
-            total: Value,
+            available: &str,
+            held: &str,
+            total: &str,
             locked: bool,
-        ) -> Client {
-            Client {
+        ) -> ClientSnapshot {
+            ClientSnapshot {
                 id: client,
-                available,
-                held,
-                total,
+                currency: None,
+                available: value(available),
+                held: value(held),
+                total: value(total),
                 locked,
-                ..Default::default()
+                credit: None,
             }
         }
+
+        pub fn value(value: &str) -> Value {
+            value.parse().unwrap()
+        }
     }
 }
@@ -0,0 +1,348 @@
+use crate::{
+    client::{AccountStatus, Balance},
+    common::ClientId,
+    input::is_currency,
+    process::Engine,
+    value::Value,
+};
+use anyhow::{bail, Result};
+use serde::Deserialize;
+use std::io;
+
+/// Client account settings. Profiles register clients before their first transaction.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Profile {
+    pub client: ClientId,
+    #[serde(default)]
+    pub status: Option<AccountStatus>,
+    /// Available funds may go negative down to minus this limit
+    #[serde(default)]
+    pub credit_limit: Option<Value>,
+    /// Currency of the account, e.g. `USD`
+    #[serde(default)]
+    pub currency: Option<String>,
+    /// Date the account was created in `YYYY-MM-DD` format
+    #[serde(default)]
+    pub created: Option<String>,
+}
+
+impl Profile {
+    pub fn new(client: ClientId) -> Self {
+        Self {
+            client,
+            status: None,
+            credit_limit: None,
+            currency: None,
+            created: None,
+        }
+    }
+
+    /// Reads client profiles from CSV.
+    pub fn read_all<R: io::Read>(input: R) -> Result<Vec<Self>> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(input);
+        let mut profiles = vec![];
+
+        for profile in reader.deserialize() {
+            let profile: Self = profile?;
+
+            profile.validate()?;
+            profiles.push(profile);
+        }
+
+        Ok(profiles)
+    }
+
+    /// Reads client profiles from JSON array.
+    pub fn read_json<R: io::Read>(input: R) -> Result<Vec<Self>> {
+        let profiles: Vec<Self> = serde_json::from_reader(input)?;
+
+        for profile in &profiles {
+            profile.validate()?;
+        }
+
+        Ok(profiles)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if matches!(self.credit_limit, Some(limit) if limit < Value::ZERO) {
+            bail!(
+                "ERROR: Client {} profile contains negative credit limit.",
+                self.client
+            );
+        }
+
+        if matches!(&self.currency, Some(currency) if !is_currency(currency)) {
+            bail!(
+                "ERROR: Client {} profile contains invalid currency.",
+                self.client
+            );
+        }
+
+        if matches!(&self.created, Some(created) if !is_date(created)) {
+            bail!(
+                "ERROR: Client {} profile contains invalid creation date.",
+                self.client
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks that the value is a `YYYY-MM-DD` date.
+pub(crate) fn is_date(value: &str) -> bool {
+    let parts: Vec<_> = value.split('-').collect();
+
+    match parts.as_slice() {
+        [year, month, day] if year.len() == 4 && month.len() == 2 && day.len() == 2 => {
+            match (year.parse::<u16>(), month.parse::<u8>(), day.parse::<u8>()) {
+                (Ok(_), Ok(month), Ok(day)) => (1..=12).contains(&month) && (1..=31).contains(&day),
+                _ => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+/// Orders account statuses from the least to the most restrictive.
+fn restriction(status: AccountStatus) -> u8 {
+    match status {
+        AccountStatus::Active => 0,
+        AccountStatus::Locked => 1,
+        AccountStatus::Frozen => 2,
+        AccountStatus::Closed => 3,
+    }
+}
+
+impl Engine {
+    /// Applies settings present in the profile to the client, client is created if it's not
+    /// known. Status of a known client can only become more restrictive, lifting a lock or
+    /// a freeze is left to the administrative operations. Currency of the account can be
+    /// changed only while it has no funds in the old and the new currency.
+    pub fn set_profile(&mut self, profile: &Profile) -> Result<()> {
+        let known = self.clients.contains_key(&profile.client);
+        let client = self.client_mut(profile.client);
+
+        if let Some(currency) = &profile.currency {
+            if client.currency.as_ref() != Some(currency) {
+                if client.balance != Balance::default()
+                    || client.balance(Some(currency)) != Balance::default()
+                {
+                    bail!(
+                        "ERROR: Cannot change currency of client {} with non-zero balance.",
+                        profile.client
+                    );
+                }
+
+                client.balances.remove(currency);
+                client.currency = Some(currency.clone());
+            }
+        }
+
+        match profile.status {
+            Some(status) if !known || restriction(status) > restriction(client.status) => {
+                client.status = status;
+            }
+            _ => (),
+        }
+
+        if let Some(credit_limit) = profile.credit_limit {
+            client.credit_limit = credit_limit;
+        }
+
+        if let Some(created) = &profile.created {
+            client.created = Some(created.clone());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        message::Message,
+        transaction::{Tx, TxType},
+    };
+
+    #[test]
+    fn test_read_all() {
+        let profiles = Profile::read_all(
+            "client, status, credit_limit, currency, created\n\
+             1, frozen, 100.5, USD, 2021-03-31\n\
+             2,,,,\n"
+                .as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            vec![
+                Profile {
+                    status: Some(AccountStatus::Frozen),
+                    credit_limit: Some("100.5".parse().unwrap()),
+                    currency: Some("USD".to_string()),
+                    created: Some("2021-03-31".to_string()),
+                    ..Profile::new(1)
+                },
+                Profile::new(2),
+            ],
+            profiles
+        );
+    }
+
+    #[test]
+    fn test_read_json() {
+        let profiles = Profile::read_json(
+            r#"[{"client": 1, "credit_limit": "10"}, {"client": 2, "status": "closed"}]"#
+                .as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            vec![
+                Profile {
+                    credit_limit: Some("10".parse().unwrap()),
+                    ..Profile::new(1)
+                },
+                Profile {
+                    status: Some(AccountStatus::Closed),
+                    ..Profile::new(2)
+                },
+            ],
+            profiles
+        );
+    }
+
+    #[test]
+    fn test_negative_credit_limit_fail() {
+        assert!(Profile::read_all("client,credit_limit\n1,-1\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_invalid_date_fail() {
+        assert!(Profile::read_all("client,created\n1,2021-13-01\n".as_bytes()).is_err());
+        assert!(Profile::read_all("client,created\n1,31.03.2021\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_set_profile() {
+        let mut engine = Engine::new();
+        let limit: Value = "10".parse().unwrap();
+
+        engine
+            .set_profile(&Profile {
+                credit_limit: Some(limit),
+                created: Some("2021-03-31".to_string()),
+                ..Profile::new(1)
+            })
+            .unwrap();
+        engine
+            .set_profile(&Profile {
+                status: Some(AccountStatus::Frozen),
+                currency: Some("USD".to_string()),
+                ..Profile::new(1)
+            })
+            .unwrap();
+
+        let client = &engine.clients[&1];
+
+        assert_eq!(AccountStatus::Frozen, client.status);
+        assert_eq!(limit, client.credit_limit);
+        assert_eq!(Some("USD"), client.currency.as_deref());
+        assert_eq!(Some("2021-03-31"), client.created.as_deref());
+    }
+
+    #[test]
+    fn test_set_profile_currency_fail() {
+        let mut engine = Engine::new();
+        let profile = Profile {
+            currency: Some("USD".to_string()),
+            ..Profile::new(1)
+        };
+
+        engine.client_mut(1).balance.available = Value::from_units(1);
+
+        assert!(engine.set_profile(&profile).is_err());
+
+        let mut engine = Engine::new();
+
+        engine
+            .client_mut(1)
+            .balances
+            .insert("USD".to_string(), Balance::default());
+        engine.set_profile(&profile).unwrap();
+
+        // Setting the same currency again is not a change
+        assert!(engine.set_profile(&profile).is_ok());
+        assert!(engine.clients[&1].balances.is_empty());
+
+        engine
+            .set_profile(&Profile {
+                currency: Some("EUR".to_string()),
+                ..Profile::new(1)
+            })
+            .unwrap();
+        engine.client_mut(1).balances.insert(
+            "USD".to_string(),
+            Balance {
+                available: Value::from_units(1),
+                ..Balance::default()
+            },
+        );
+
+        assert!(engine.set_profile(&profile).is_err());
+    }
+
+    #[test]
+    fn test_set_profile_status() {
+        let mut engine = Engine::new();
+        let amount = Value::from_units(10_000);
+
+        for tx in &[
+            Tx::new(TxType::Deposit, 1, 1, amount),
+            Tx::new(TxType::Dispute, 1, 1, Value::ZERO),
+            Tx::new(TxType::Chargeback, 1, 1, Value::ZERO),
+        ] {
+            engine.process(tx, &mut vec![]);
+        }
+
+        let mut state = vec![];
+
+        engine.save_state(&mut state).unwrap();
+
+        let mut engine = Engine::load_state(state.as_slice()).unwrap();
+        let mut messages = vec![];
+
+        let profile = |status| Profile {
+            status: Some(status),
+            ..Profile::new(1)
+        };
+
+        // Profile of a known client cannot lift the lock of the chargeback
+        engine.set_profile(&profile(AccountStatus::Active)).unwrap();
+        engine.process(&Tx::new(TxType::Deposit, 1, 2, amount), &mut messages);
+
+        assert_eq!(AccountStatus::Locked, engine.clients[&1].status);
+        assert_eq!(
+            vec![Message::AccountIsLocked(1, 2, TxType::Deposit)],
+            messages
+        );
+
+        engine.set_profile(&profile(AccountStatus::Closed)).unwrap();
+        engine.set_profile(&profile(AccountStatus::Frozen)).unwrap();
+
+        assert_eq!(AccountStatus::Closed, engine.clients[&1].status);
+
+        engine
+            .set_profile(&Profile {
+                status: Some(AccountStatus::Locked),
+                ..Profile::new(2)
+            })
+            .unwrap();
+
+        assert_eq!(AccountStatus::Locked, engine.clients[&2].status);
+    }
+}
@@ -0,0 +1,150 @@
+use crate::{input::is_currency, profile::is_date, value::Value};
+use anyhow::{bail, Result};
+use serde::Deserialize;
+use std::io;
+
+/// Exchange rate between two currencies, valid from the `valid_from` date until the day
+/// before the `valid_until` date. Missing bound leaves the window open on that side.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Rate {
+    pub from: String,
+    pub to: String,
+    /// Units of the `to` currency for one unit of the `from` currency
+    pub rate: Value,
+    #[serde(default)]
+    pub valid_from: Option<String>,
+    #[serde(default)]
+    pub valid_until: Option<String>,
+}
+
+impl Rate {
+    /// Checks that the rate is valid on the `date`. Transaction without date can only use
+    /// rates without a validity window.
+    fn is_valid(&self, date: Option<&str>) -> bool {
+        match date {
+            Some(date) => {
+                self.valid_from.as_deref().is_none_or(|from| from <= date)
+                    && self.valid_until.as_deref().is_none_or(|until| date < until)
+            }
+            None => self.valid_from.is_none() && self.valid_until.is_none(),
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        if !is_currency(&self.from) || !is_currency(&self.to) || self.from == self.to {
+            bail!(
+                "ERROR: Rate {} to {} contains invalid currency.",
+                self.from,
+                self.to
+            );
+        }
+
+        if !self.rate.is_positive() {
+            bail!("ERROR: Rate {} to {} is not positive.", self.from, self.to);
+        }
+
+        let dates = [&self.valid_from, &self.valid_until];
+
+        if dates
+            .iter()
+            .any(|d| matches!(d, Some(date) if !is_date(date)))
+            || matches!(dates, [Some(from), Some(until)] if from >= until)
+        {
+            bail!(
+                "ERROR: Rate {} to {} contains invalid validity window.",
+                self.from,
+                self.to
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Exchange rates used by the `exchange` transactions.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RateTable {
+    pub rates: Vec<Rate>,
+}
+
+impl RateTable {
+    /// Reads exchange rates from CSV with `from,to,rate,valid_from,valid_until` columns.
+    pub fn read_all<R: io::Read>(input: R) -> Result<Self> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(input);
+        let mut rates = vec![];
+
+        for rate in reader.deserialize() {
+            let rate: Rate = rate?;
+
+            rate.validate()?;
+            rates.push(rate);
+        }
+
+        Ok(Self { rates })
+    }
+
+    /// Rate from one currency to another on the `date`. If several rates are valid, the one
+    /// with the latest start of the window wins, and the later row of the table on a tie.
+    pub fn rate(&self, from: &str, to: &str, date: Option<&str>) -> Option<Value> {
+        self.rates
+            .iter()
+            .filter(|r| r.from == from && r.to == to && r.is_valid(date))
+            .max_by_key(|r| r.valid_from.as_deref())
+            .map(|r| r.rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> RateTable {
+        RateTable::read_all(
+            "from, to, rate, valid_from, valid_until\n\
+             EUR, USD, 1.1, , \n\
+             EUR, USD, 1.2, 2021-01-01, 2021-02-01\n\
+             EUR, USD, 1.25, 2021-01-15, 2021-01-16\n\
+             USD, EUR, 0.9, 2021-01-01,\n"
+                .as_bytes(),
+        )
+        .unwrap()
+    }
+
+    fn rate(from: &str, to: &str, date: Option<&str>) -> Option<Value> {
+        table().rate(from, to, date)
+    }
+
+    fn v(value: &str) -> Option<Value> {
+        Some(value.parse().unwrap())
+    }
+
+    #[test]
+    fn test_rate() {
+        assert_eq!(v("1.1"), rate("EUR", "USD", None));
+        assert_eq!(v("1.1"), rate("EUR", "USD", Some("2020-12-31")));
+        assert_eq!(v("1.2"), rate("EUR", "USD", Some("2021-01-01")));
+        assert_eq!(v("1.25"), rate("EUR", "USD", Some("2021-01-15")));
+        assert_eq!(v("1.1"), rate("EUR", "USD", Some("2021-02-01")));
+        assert_eq!(v("0.9"), rate("USD", "EUR", Some("2022-01-01")));
+        assert_eq!(None, rate("USD", "EUR", Some("2020-01-01")));
+        assert_eq!(None, rate("USD", "EUR", None));
+        assert_eq!(None, rate("EUR", "GBP", None));
+    }
+
+    #[test]
+    fn test_invalid_rate_fail() {
+        for rates in &[
+            "EUR,USD,0,,",
+            "EUR,EUR,1,,",
+            "EUR,usd,1,,",
+            "EUR,USD,1,2021-02-01,2021-01-01",
+            "EUR,USD,1,2021-02-30x,",
+        ] {
+            let input = format!("from,to,rate,valid_from,valid_until\n{}\n", rates);
+
+            assert!(RateTable::read_all(input.as_bytes()).is_err(), "{}", rates);
+        }
+    }
+}
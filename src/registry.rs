@@ -0,0 +1,82 @@
+use crate::{
+    common::ClientId,
+    message::Message,
+    transaction::{Tx, TxId, TxType},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Engine-wide registry of transaction ids. Transaction ids are globally unique so each id
+/// belongs to the client that used it first for a deposit, withdrawal or transfer, whether the
+/// transaction was accepted or not.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Registry {
+    owners: HashMap<TxId, ClientId>,
+    /// Destination clients of accepted transfers
+    #[serde(default)]
+    destinations: HashMap<TxId, ClientId>,
+}
+
+impl Registry {
+    /// Client that owns the transaction id.
+    pub fn owner(&self, tx_id: TxId) -> Option<ClientId> {
+        self.owners.get(&tx_id).copied()
+    }
+
+    /// Other client whose funds are changed by the transaction: destination of the transfer
+    /// or of the disputed transfer.
+    pub fn counterparty(&self, tx: &Tx) -> Option<ClientId> {
+        match tx.ty {
+            TxType::Transfer => tx.destination(),
+            TxType::Dispute | TxType::Resolve | TxType::Chargeback => {
+                self.destinations.get(&tx.tx_id).copied()
+            }
+            _ => None,
+        }
+    }
+
+    /// Records destination of the accepted transfer so its disputes involve both clients.
+    pub fn accept(&mut self, tx: &Tx) {
+        if let (TxType::Transfer, Some(destination)) = (tx.ty, tx.destination()) {
+            self.destinations.insert(tx.tx_id, destination);
+        }
+    }
+
+    /// Checks that transaction doesn't use or refer to an id of another client.
+    pub fn validate(&mut self, tx: &Tx, messages: &mut Vec<Message>) -> bool {
+        if tx.ty == TxType::Transfer
+            && (tx.destination().is_none() || tx.destination() == Some(tx.client_id))
+        {
+            messages.push(Message::InvalidDestination(tx.client_id, tx.tx_id, tx.ty));
+            return false;
+        }
+
+        match tx.ty {
+            TxType::Deposit | TxType::Withdrawal | TxType::Transfer | TxType::Exchange => {
+                let owner = *self.owners.entry(tx.tx_id).or_insert(tx.client_id);
+
+                if owner != tx.client_id {
+                    messages.push(Message::TransactionExistForOtherClient(
+                        tx.client_id,
+                        tx.tx_id,
+                        tx.ty,
+                    ));
+                    return false;
+                }
+            }
+            TxType::Dispute | TxType::Resolve | TxType::Chargeback => {
+                if matches!(self.owners.get(&tx.tx_id), Some(&owner) if owner != tx.client_id) {
+                    messages.push(Message::TransactionOfOtherClient(
+                        tx.client_id,
+                        tx.tx_id,
+                        tx.ty,
+                    ));
+                    return false;
+                }
+            }
+            TxType::Unlock | TxType::Freeze | TxType::Close | TxType::Fee => (),
+        }
+
+        true
+    }
+}
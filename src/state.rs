@@ -0,0 +1,157 @@
+use crate::{client::Client, process::Engine, registry::Registry};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+
+/// Version of the engine state format. Increment it on every incompatible change, new fields
+/// with defaults keep older states loadable.
+pub const STATE_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct StateRef<'a> {
+    version: u32,
+    /// Number of processed transactions, journal entries up to it are already in the state
+    seq: u64,
+    clients: Vec<&'a Client>,
+    registry: &'a Registry,
+}
+
+#[derive(Deserialize)]
+struct State {
+    #[serde(default)]
+    seq: u64,
+    clients: Vec<Client>,
+    registry: Registry,
+}
+
+#[derive(Deserialize)]
+struct StateVersion {
+    version: u32,
+}
+
+impl Engine {
+    /// Writes full engine state including transactions history of all clients.
+    pub fn save_state<W: Write>(&self, writer: W) -> Result<()> {
+        let mut clients: Vec<_> = self.clients.values().collect();
+
+        clients.sort_by_key(|c| c.id);
+
+        let state = StateRef {
+            version: STATE_VERSION,
+            seq: self.seq,
+            clients,
+            registry: &self.registry,
+        };
+
+        serde_json::to_writer(writer, &state)?;
+
+        Ok(())
+    }
+
+    /// Restores engine from the state written by `save_state`.
+    pub fn load_state<R: Read>(reader: R) -> Result<Self> {
+        let state: serde_json::Value = serde_json::from_reader(reader)?;
+        let StateVersion { version } = StateVersion::deserialize(&state)?;
+
+        if version != STATE_VERSION {
+            bail!(
+                "ERROR: Unsupported state version {}, expected {}.",
+                version,
+                STATE_VERSION
+            );
+        }
+
+        let state = State::deserialize(state)?;
+
+        Ok(Self {
+            clients: state.clients.into_iter().map(|c| (c.id, c)).collect(),
+            registry: state.registry,
+            seq: state.seq,
+            ..Self::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        message::Message,
+        transaction::{Tx, TxType},
+        value::Value,
+    };
+
+    fn process(engine: &mut Engine, transactions: &[Tx]) -> Vec<Message> {
+        let mut messages = vec![];
+
+        for tx in transactions {
+            engine.process(tx, &mut messages);
+        }
+
+        messages
+    }
+
+    fn reload(engine: &Engine) -> Engine {
+        let mut state = vec![];
+
+        engine.save_state(&mut state).unwrap();
+
+        Engine::load_state(state.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn test_continue_from_state() {
+        let amount = Value::from_units(10_000);
+        let mut engine = Engine::new();
+
+        process(
+            &mut engine,
+            &[
+                Tx::new(TxType::Deposit, 1, 1, amount),
+                Tx::new(TxType::Deposit, 1, 2, amount),
+                Tx::new(TxType::Dispute, 1, 2, Value::ZERO),
+                Tx::new(TxType::Deposit, 2, 3, amount),
+            ],
+        );
+
+        let mut engine = reload(&engine);
+        let messages = process(
+            &mut engine,
+            &[
+                Tx::new(TxType::Resolve, 1, 2, Value::ZERO),
+                Tx::new(TxType::Dispute, 1, 1, Value::ZERO),
+                Tx::new(TxType::Deposit, 2, 1, amount),
+                Tx::new(TxType::Dispute, 1, 2, Value::ZERO),
+            ],
+        );
+
+        assert_eq!(
+            vec![
+                Message::TransactionExistForOtherClient(2, 1, TxType::Deposit),
+                Message::DisputeLimitReached(1, 2, TxType::Deposit),
+            ],
+            messages
+        );
+
+        let client = engine.client(1).unwrap();
+
+        assert_eq!(amount, client.available);
+        assert_eq!(amount, client.held);
+        assert_eq!(amount + amount, client.total);
+    }
+
+    #[test]
+    fn test_state_without_new_fields() {
+        let state = r#"{"version": 1, "clients": [], "registry": {"owners": {}}}"#;
+        let engine = Engine::load_state(state.as_bytes()).unwrap();
+
+        assert_eq!(0, engine.seq());
+    }
+
+    #[test]
+    fn test_unsupported_version_fail() {
+        let state = r#"{"version": 2, "clients": [], "registry": {"owners": {}}}"#;
+
+        assert!(Engine::load_state(state.as_bytes()).is_err());
+    }
+}
@@ -1,50 +1,287 @@
-use crate::common::{ClientId, Value};
-use serde::Deserialize;
+use crate::{common::ClientId, config::WithdrawalDisputePolicy, value::Value};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
 pub enum TxType {
     #[serde(rename = "deposit")]
     Deposit,
     #[serde(rename = "withdrawal")]
     Withdrawal,
+    /// Moves funds from the client to the destination client
+    #[serde(rename = "transfer")]
+    Transfer,
+    /// Converts funds of the client from one currency to another
+    #[serde(rename = "exchange")]
+    Exchange,
     #[serde(rename = "dispute")]
     Dispute,
     #[serde(rename = "resolve")]
     Resolve,
     #[serde(rename = "chargeback")]
     Chargeback,
+    /// Administrative operation that clears the lock or freeze of the account
+    #[serde(rename = "unlock")]
+    Unlock,
+    /// Administrative operation that blocks the account until it is unlocked
+    #[serde(rename = "freeze")]
+    Freeze,
+    /// Administrative operation that blocks the account permanently
+    #[serde(rename = "close")]
+    Close,
+    /// Fee charged by the system for the transaction with the same id
+    #[serde(rename = "fee")]
+    Fee,
+}
+
+impl TxType {
+    /// Checks that transaction is an administrative operation on the account.
+    pub fn is_admin(self) -> bool {
+        matches!(self, Self::Unlock | Self::Freeze | Self::Close)
+    }
 }
 
 pub type TxId = u32;
 
-#[derive(Debug, Clone)]
+/// Outcome of a deposit or withdrawal transaction.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
 pub enum TxState {
+    #[default]
     Active,
     InDispute,
-    Disputed,
+    /// Dispute was resolved and funds were returned to the client, transaction can be
+    /// disputed again while under the limit
+    Resolved,
+    /// Dispute ended with the chargeback and transaction was reversed
+    ChargedBack,
+    /// Withdrawal reversed to cover a dispute of an earlier deposit
+    ClawedBack,
 }
 
-impl Default for TxState {
-    fn default() -> Self {
-        Self::Active
-    }
-}
-
-#[derive(Debug, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Tx {
     pub ty: TxType,
     pub client_id: ClientId,
     pub tx_id: TxId,
+    /// Part of the transaction amount for dispute, resolve and chargeback, zero if not given
     pub amount: Value,
     pub state: TxState,
+    /// Dispute cycles of the transaction in the order they were opened. Several partial
+    /// disputes can be open at the same time.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disputes: Vec<DisputeCycle>,
+    /// Fields used only by some transactions, kept out of line so the retained deposits and
+    /// withdrawals stay small. `None` if none of the fields is set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<Box<TxDetails>>,
+}
+
+/// Transfer, administrative operation, currency and exchange fields of the transaction.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TxDetails {
+    /// Client receiving the transfer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destination: Option<ClientId>,
+    /// Reason code of the administrative operation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Currency code, `None` for the account currency of the client
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+    /// Currency the exchange converts funds to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to_currency: Option<String>,
+    /// Date of the exchange in `YYYY-MM-DD` format that selects the exchange rate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    /// Exchange rate applied to the accepted exchange
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate: Option<Value>,
+}
+
+/// Single dispute of a transaction.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DisputeCycle {
+    /// Disputed part of the transaction amount
+    pub amount: Value,
+    /// Resolve or chargeback that closed the dispute, `None` while the transaction is in dispute
+    pub outcome: Option<TxType>,
+    /// Withdrawals clawed back to cover the dispute, they are restored if it's resolved
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub clawed_back: Vec<TxId>,
 }
 
 impl Tx {
-    pub fn dispute_amount(&self) -> Value {
-        match self.ty {
-            TxType::Deposit => self.amount,
-            TxType::Withdrawal => -self.amount,
-            _ => unreachable!(),
+    pub fn new(ty: TxType, client_id: ClientId, tx_id: TxId, amount: Value) -> Self {
+        Self {
+            ty,
+            client_id,
+            tx_id,
+            amount,
+            state: TxState::Active,
+            disputes: vec![],
+            details: None,
+        }
+    }
+
+    /// Sets the optional fields of the transaction.
+    pub fn with_details(self, details: TxDetails) -> Self {
+        Self {
+            details: Some(Box::new(details)).filter(|d| **d != TxDetails::default()),
+            ..self
         }
     }
+
+    /// Optional fields of the transaction for update, they are created if not set yet.
+    pub fn details_mut(&mut self) -> &mut TxDetails {
+        self.details.get_or_insert_with(Default::default)
+    }
+
+    /// Client receiving the transfer.
+    pub fn destination(&self) -> Option<ClientId> {
+        self.details.as_ref().and_then(|d| d.destination)
+    }
+
+    /// Reason code of the administrative operation.
+    pub fn reason(&self) -> Option<&str> {
+        self.details.as_ref().and_then(|d| d.reason.as_deref())
+    }
+
+    /// Currency code, `None` for the account currency of the client.
+    pub fn currency(&self) -> Option<&str> {
+        self.details.as_ref().and_then(|d| d.currency.as_deref())
+    }
+
+    /// Currency the exchange converts funds to.
+    pub fn to_currency(&self) -> Option<&str> {
+        self.details.as_ref().and_then(|d| d.to_currency.as_deref())
+    }
+
+    /// Date of the exchange that selects the exchange rate.
+    pub fn date(&self) -> Option<&str> {
+        self.details.as_ref().and_then(|d| d.date.as_deref())
+    }
+
+    /// Exchange rate applied to the accepted exchange.
+    pub fn rate(&self) -> Option<Value> {
+        self.details.as_ref().and_then(|d| d.rate)
+    }
+
+    /// Sum of disputes that are not resolved or charged back yet, `None` on overflow.
+    pub fn open_disputes(&self) -> Option<Value> {
+        self.disputes
+            .iter()
+            .filter(|c| c.outcome.is_none())
+            .try_fold(Value::ZERO, |sum, c| sum.checked_add(c.amount))
+    }
+
+    /// Checks that disputing `amount` more keeps every part of the transaction amount disputed
+    /// at most `max_disputes` times.
+    pub fn can_dispute(&self, amount: Value, max_disputes: u32) -> bool {
+        let disputed = self
+            .disputes
+            .iter()
+            .try_fold(amount, |sum, c| sum.checked_add(c.amount));
+
+        match (disputed, self.amount.checked_mul(i64::from(max_disputes))) {
+            (Some(disputed), Some(limit)) => disputed <= limit,
+            (_, None) => true,
+            (None, _) => false,
+        }
+    }
+
+    /// Updates state from the dispute cycles.
+    pub fn update_state(&mut self) {
+        let outcome = |ty| self.disputes.iter().any(|c| c.outcome == Some(ty));
+
+        self.state = if self.disputes.iter().any(|c| c.outcome.is_none()) {
+            TxState::InDispute
+        } else if outcome(TxType::Chargeback) {
+            TxState::ChargedBack
+        } else if outcome(TxType::Resolve) {
+            TxState::Resolved
+        } else {
+            TxState::Active
+        };
+    }
+
+    /// Amount and currency of the client funds moved by the `amount` of this transaction.
+    /// Disputes of exchange move the converted amount in its target currency.
+    pub fn funds(&self, amount: Value) -> (Value, Option<&str>) {
+        match (self.ty, self.rate()) {
+            (TxType::Exchange, Some(rate)) => (
+                amount.checked_convert(rate).unwrap_or_default(),
+                self.to_currency(),
+            ),
+            _ => (amount, self.currency()),
+        }
+    }
+
+    /// Change of client funds caused by the dispute operation `op` on the `amount` of this
+    /// transaction, `None` on overflow. For transfer it's the change of the destination client
+    /// funds, for exchange it's the change of funds in the target currency.
+    pub fn dispute_change(
+        &self,
+        op: TxType,
+        amount: Value,
+        policy: WithdrawalDisputePolicy,
+    ) -> Option<FundsChange> {
+        let change = match (self.ty, policy) {
+            (TxType::Withdrawal, WithdrawalDisputePolicy::HoldCredit) => {
+                let negative = amount.checked_neg()?;
+
+                match op {
+                    TxType::Dispute => FundsChange::new(Value::ZERO, amount, amount),
+                    TxType::Resolve => FundsChange::new(Value::ZERO, negative, negative),
+                    TxType::Chargeback => FundsChange::new(amount, negative, Value::ZERO),
+                    _ => unreachable!(),
+                }
+            }
+            _ => {
+                let amount = match self.ty {
+                    TxType::Deposit | TxType::Transfer => amount,
+                    TxType::Exchange => self.funds(amount).0,
+                    TxType::Withdrawal => amount.checked_neg()?,
+                    _ => unreachable!(),
+                };
+                let negative = amount.checked_neg()?;
+
+                match op {
+                    TxType::Dispute => FundsChange::new(negative, amount, Value::ZERO),
+                    TxType::Resolve => FundsChange::new(amount, negative, Value::ZERO),
+                    TxType::Chargeback => FundsChange::new(Value::ZERO, negative, negative),
+                    _ => unreachable!(),
+                }
+            }
+        };
+
+        Some(change)
+    }
+}
+
+/// Change of available, held and total client funds.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FundsChange {
+    pub available: Value,
+    pub held: Value,
+    pub total: Value,
+}
+
+impl FundsChange {
+    pub fn new(available: Value, held: Value, total: Value) -> Self {
+        Self {
+            available,
+            held,
+            total,
+        }
+    }
+
+    /// Adds the `amount` to available and total funds.
+    pub fn credit(amount: Value) -> Self {
+        Self::new(amount, Value::ZERO, amount)
+    }
+
+    /// Takes the `amount` from available and total funds, `None` on overflow.
+    pub fn debit(amount: Value) -> Option<Self> {
+        amount.checked_neg().map(Self::credit)
+    }
 }
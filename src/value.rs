@@ -0,0 +1,262 @@
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{convert::TryFrom, fmt, str::FromStr};
+
+/// Fixed-point monetary value with four decimal places.
+///
+/// Stored as a signed number of ten-thousandths so that sums are exact. Arithmetic is done
+/// with `checked_*` methods, operators that panic on overflow are only available in tests.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Value(i64);
+
+impl Value {
+    pub const PRECISION: usize = 4;
+    pub const ZERO: Self = Self::from_units(0);
+
+    const SCALE: i64 = 10_000;
+
+    /// Creates value from the number of ten-thousandths.
+    pub const fn from_units(units: i64) -> Self {
+        Self(units)
+    }
+
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        self.0.checked_add(rhs.0).map(Self)
+    }
+
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        self.0.checked_sub(rhs.0).map(Self)
+    }
+
+    pub fn checked_mul(self, rhs: i64) -> Option<Self> {
+        self.0.checked_mul(rhs).map(Self)
+    }
+
+    /// Returns `percent` percents of the value rounded half up to the precision.
+    pub fn checked_percent(self, percent: Self) -> Option<Self> {
+        let scale = 100 * Self::SCALE as i128;
+        let units = (self.0 as i128 * percent.0 as i128 + scale / 2).div_euclid(scale);
+
+        i64::try_from(units).ok().map(Self)
+    }
+
+    /// Returns the value converted with the exchange `rate` rounded half up to the precision.
+    pub fn checked_convert(self, rate: Self) -> Option<Self> {
+        let scale = Self::SCALE as i128;
+        let units = (self.0 as i128 * rate.0 as i128 + scale / 2).div_euclid(scale);
+
+        i64::try_from(units).ok().map(Self)
+    }
+
+    pub fn checked_neg(self) -> Option<Self> {
+        self.0.checked_neg().map(Self)
+    }
+
+    pub fn is_positive(self) -> bool {
+        self.0 > 0
+    }
+}
+
+#[cfg(test)]
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+
+#[cfg(test)]
+impl Add for Value {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        self.checked_add(rhs).expect("Value overflow")
+    }
+}
+
+#[cfg(test)]
+impl Sub for Value {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        self.checked_sub(rhs).expect("Value overflow")
+    }
+}
+
+#[cfg(test)]
+impl Neg for Value {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        self.checked_neg().expect("Value overflow")
+    }
+}
+
+#[cfg(test)]
+impl AddAssign for Value {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+#[cfg(test)]
+impl SubAssign for Value {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseValueError {
+    Empty,
+    InvalidDigit,
+    TooManyDecimals,
+    Overflow,
+}
+
+impl fmt::Display for ParseValueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => f.write_str("cannot parse value from empty string"),
+            Self::InvalidDigit => f.write_str("invalid digit found in value"),
+            Self::TooManyDecimals => {
+                write!(f, "value has more than {} decimal places", Value::PRECISION)
+            }
+            Self::Overflow => f.write_str("value is too large"),
+        }
+    }
+}
+
+impl std::error::Error for ParseValueError {}
+
+impl FromStr for Value {
+    type Err = ParseValueError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (negative, s) = match s.as_bytes().first() {
+            Some(b'-') => (true, &s[1..]),
+            Some(b'+') => (false, &s[1..]),
+            _ => (false, s),
+        };
+        let (int, frac) = match s.find('.') {
+            Some(pos) => (&s[..pos], &s[pos + 1..]),
+            None => (s, ""),
+        };
+
+        if int.is_empty() && frac.is_empty() {
+            return Err(ParseValueError::Empty);
+        }
+        if !int.bytes().chain(frac.bytes()).all(|b| b.is_ascii_digit()) {
+            return Err(ParseValueError::InvalidDigit);
+        }
+        if frac.len() > Self::PRECISION {
+            return Err(ParseValueError::TooManyDecimals);
+        }
+
+        let units = int
+            .bytes()
+            .chain(frac.bytes())
+            .chain(std::iter::repeat_n(b'0', Self::PRECISION - frac.len()))
+            .try_fold(0i64, |acc, b| {
+                acc.checked_mul(10)?.checked_add((b - b'0') as i64)
+            })
+            .ok_or(ParseValueError::Overflow)?;
+
+        Ok(Self(if negative { -units } else { units }))
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let units = self.0.unsigned_abs();
+        let scale = Self::SCALE as u64;
+
+        write!(
+            f,
+            "{}{}.{:0width$}",
+            sign,
+            units / scale,
+            units % scale,
+            width = Self::PRECISION
+        )
+    }
+}
+
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(s: &str) -> Value {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Value::from_units(10_000), v("1"));
+        assert_eq!(Value::from_units(15_000), v("1.5"));
+        assert_eq!(Value::from_units(12_345), v("1.2345"));
+        assert_eq!(Value::from_units(5_000), v(".5"));
+        assert_eq!(Value::from_units(-5_000), v("-0.5"));
+        assert_eq!(Value::from_units(20_000), v("2."));
+    }
+
+    #[test]
+    fn test_parse_fail() {
+        assert_eq!(Err(ParseValueError::Empty), "".parse::<Value>());
+        assert_eq!(Err(ParseValueError::Empty), ".".parse::<Value>());
+        assert_eq!(Err(ParseValueError::InvalidDigit), "1e5".parse::<Value>());
+        assert_eq!(Err(ParseValueError::InvalidDigit), "1.-5".parse::<Value>());
+        assert_eq!(
+            Err(ParseValueError::TooManyDecimals),
+            "1.23456".parse::<Value>()
+        );
+        assert_eq!(
+            Err(ParseValueError::Overflow),
+            "99999999999999999999".parse::<Value>()
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!("0.0000", Value::ZERO.to_string());
+        assert_eq!("1.5000", v("1.5").to_string());
+        assert_eq!("-0.0500", v("-0.05").to_string());
+        assert_eq!("16777217.0100", v("16777217.01").to_string());
+    }
+
+    #[test]
+    fn test_exact_sum() {
+        assert_eq!(v("0.3"), v("0.1") + v("0.2"));
+        assert_eq!(None, Value::from_units(i64::MAX).checked_add(v("0.0001")));
+    }
+
+    #[test]
+    fn test_percent() {
+        assert_eq!(Some(v("1.5")), v("100").checked_percent(v("1.5")));
+        assert_eq!(Some(v("0.0001")), v("0.01").checked_percent(v("0.5")));
+        assert_eq!(Some(v("0")), v("0.01").checked_percent(v("0.4999")));
+        assert_eq!(None, Value::from_units(i64::MAX).checked_percent(v("200")));
+    }
+
+    #[test]
+    fn test_convert() {
+        assert_eq!(Some(v("11")), v("10").checked_convert(v("1.1")));
+        assert_eq!(Some(v("0.0001")), v("0.0001").checked_convert(v("0.5")));
+        assert_eq!(Some(v("0")), v("0.0001").checked_convert(v("0.4999")));
+        assert_eq!(None, Value::from_units(i64::MAX).checked_convert(v("2")));
+    }
+}